@@ -9,6 +9,7 @@
 [FileDesc]: https://docs.rs/filedesc/latest/filedesc/struct.FileDesc.html
 [FileDesc::duplicate]: https://docs.rs/filedesc/latest/filedesc/struct.FileDesc.html#method.duplicate
 [FileDesc::duplicate_from]: https://docs.rs/filedesc/latest/filedesc/struct.FileDesc.html#method.duplicate_from
+[FileDesc::duplicate_inheritable]: https://docs.rs/filedesc/latest/filedesc/struct.FileDesc.html#method.duplicate_inheritable
 [FileDesc::duplicate_raw_fd]: https://docs.rs/filedesc/latest/filedesc/struct.FileDesc.html#method.duplicate_raw_fd
 [FileDesc::from_raw_fd]: https://docs.rs/filedesc/latest/filedesc/struct.FileDesc.html#method.from_raw_fd
 [FileDesc::get_close_on_exec]: https://docs.rs/filedesc/latest/filedesc/struct.FileDesc.html#method.get_close_on_exec
@@ -1,4 +1,4 @@
-//! This crate exposes a single type: [`FileDesc`][FileDesc],
+//! The main type of this crate is [`FileDesc`][FileDesc],
 //! which acts as a thin wrapper around open file descriptors.
 //! The wrapped file descriptor is closed when the wrapper is dropped.
 //!
@@ -8,8 +8,9 @@
 //! Wrapped file descriptors can also be duplicated with the [`duplicate()`][FileDesc::duplicate] function.
 //!
 //! # Close-on-exec
-//! Whenever the library duplicates a file descriptor, it tries to set the `close-on-exec` flag atomically.
+//! Whenever the library creates or duplicates a file descriptor, it tries to set the `close-on-exec` flag atomically.
 //! On platforms where this is not supported, the library falls back to setting the flag non-atomically.
+//! The only exceptions are functions that explicitly create inheritable file descriptors, like [`duplicate_inheritable()`][FileDesc::duplicate_inheritable].
 //! When an existing file descriptor is wrapped, the `close-on-exec` flag is left as it was.
 //!
 //! You can also check or set the `close-on-exec` flag with the [`get_close_on_exec()`][FileDesc::get_close_on_exec]
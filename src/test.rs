@@ -1,6 +1,47 @@
 use crate::FileDesc;
 use assert2::{assert, let_assert};
 
+/// Get a unique path in the temporary directory.
+fn temp_path() -> std::path::PathBuf {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	static COUNTER: AtomicUsize = AtomicUsize::new(0);
+	let name = format!("filedesc-test-{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed));
+	std::env::temp_dir().join(name)
+}
+
+/// Temporary directory that is removed when dropped.
+struct TempDir {
+	path: std::path::PathBuf,
+}
+
+impl TempDir {
+	fn new() -> Self {
+		let path = temp_path();
+		std::fs::create_dir(&path).unwrap();
+		Self { path }
+	}
+
+	fn path(&self) -> &std::path::Path {
+		&self.path
+	}
+}
+
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_dir_all(&self.path);
+	}
+}
+
+/// Create an anonymous temporary file.
+///
+/// The file is unlinked immediately after creating it.
+fn temp_file() -> FileDesc {
+	let path = temp_path();
+	let file = std::fs::OpenOptions::new().read(true).write(true).create_new(true).open(&path).unwrap();
+	std::fs::remove_file(&path).unwrap();
+	FileDesc::new(file.into())
+}
+
 #[test]
 fn test_get_close_on_exec() {
 	let fd = unsafe { FileDesc::duplicate_raw_fd(2i32).unwrap() };
@@ -10,9 +51,21 @@ fn test_get_close_on_exec() {
 	assert!(let Ok(_) = fd.duplicate());
 }
 
+#[test]
+fn duplicate_inheritable() {
+	let_assert!(Ok(fd) = FileDesc::duplicate_inheritable_from(std::io::stderr()));
+	assert!(let Ok(false) = fd.get_close_on_exec());
+	let_assert!(Ok(dup) = fd.duplicate());
+	assert!(let Ok(true) = dup.get_close_on_exec());
+	let_assert!(Ok(dup) = dup.duplicate_inheritable());
+	assert!(let Ok(false) = dup.get_close_on_exec());
+	let_assert!(Ok(dup) = unsafe { FileDesc::duplicate_inheritable_raw_fd(dup.as_raw_fd()) });
+	assert!(let Ok(false) = dup.get_close_on_exec());
+}
+
 #[test]
 fn duplicate_convert_stdout() {
-	let_assert!(Ok(fd) = FileDesc::duplicate_from(&std::io::stdout()));
+	let_assert!(Ok(fd) = FileDesc::duplicate_from(std::io::stdout()));
 	assert!(fd.as_raw_fd() != 0);
 	assert!(fd.as_raw_fd() != 1);
 	assert!(fd.as_raw_fd() != 2);
@@ -21,3 +74,972 @@ fn duplicate_convert_stdout() {
 	let fd = FileDesc::new(fd.into_fd());
 	assert!(fd.as_raw_fd() == raw);
 }
+
+#[test]
+fn read_write() {
+	use std::io::{IoSlice, IoSliceMut, Read, Write};
+	use std::os::unix::net::UnixStream;
+
+	let_assert!(Ok((a, b)) = UnixStream::pair());
+	let mut a = FileDesc::new(a.into());
+	let mut b = FileDesc::new(b.into());
+
+	assert!(let Ok(5) = a.write(b"hello"));
+	let mut buf = [0u8; 16];
+	assert!(let Ok(5) = b.read(&mut buf));
+	assert!(&buf[..5] == b"hello");
+
+	assert!(let Ok(11) = (&b).write_vectored(&[IoSlice::new(b"hello "), IoSlice::new(b"world")]));
+	let mut first = [0u8; 6];
+	let mut second = [0u8; 5];
+	assert!(let Ok(11) = (&a).read_vectored(&mut [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)]));
+	assert!(&first == b"hello ");
+	assert!(&second == b"world");
+}
+
+#[test]
+fn read_write_at() {
+	use std::io::Read;
+
+	let mut fd = temp_file();
+	assert!(let Ok(()) = fd.write_all_at(b"world", 6));
+	assert!(let Ok(()) = fd.write_all_at(b"hello ", 0));
+
+	let mut buf = [0u8; 5];
+	assert!(let Ok(()) = fd.read_exact_at(&mut buf, 6));
+	assert!(&buf == b"world");
+
+	// The file position is not affected.
+	let mut contents = Vec::new();
+	assert!(let Ok(11) = fd.read_to_end(&mut contents));
+	assert!(contents == b"hello world");
+
+	let_assert!(Err(e) = fd.read_exact_at(&mut buf, 8));
+	assert!(e.kind() == std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn status_flags() {
+	use crate::AccessMode;
+
+	let fd = temp_file();
+	let_assert!(Ok(dup) = fd.duplicate());
+	assert!(let Ok(false) = fd.get_nonblocking());
+	assert!(let Ok(false) = fd.get_append());
+	assert!(let Ok(AccessMode::ReadWrite) = fd.access_mode());
+
+	// Status flags are shared with duplicates and changing one flag leaves the others alone.
+	assert!(let Ok(()) = fd.set_nonblocking(true));
+	assert!(let Ok(()) = fd.set_append(true));
+	assert!(let Ok(true) = dup.get_nonblocking());
+	assert!(let Ok(true) = dup.get_append());
+	assert!(let Ok(()) = dup.set_nonblocking(false));
+	assert!(let Ok(false) = fd.get_nonblocking());
+	assert!(let Ok(true) = fd.get_append());
+	assert!(let Ok(AccessMode::ReadWrite) = fd.access_mode());
+
+	let_assert!(Ok(null) = std::fs::File::open("/dev/null"));
+	let null = FileDesc::new(null.into());
+	assert!(let Ok(AccessMode::ReadOnly) = null.access_mode());
+}
+
+#[test]
+fn duplicate_to_and_at_least() {
+	use std::io::Read;
+
+	let fd = temp_file();
+	assert!(let Ok(()) = fd.write_all_at(b"hello", 0));
+
+	let_assert!(Ok(high) = fd.duplicate_at_least(100));
+	assert!(high.as_raw_fd() >= 100);
+	assert!(let Ok(true) = high.get_close_on_exec());
+
+	// Duplicate onto a number we own, replacing the file it refers to.
+	let_assert!(Ok(target) = fd.duplicate_at_least(200));
+	let target_raw = target.as_raw_fd();
+	let_assert!(Ok(null) = std::fs::File::open("/dev/null"));
+	let null = FileDesc::new(null.into());
+	let_assert!(Ok(mut target) = unsafe { null.duplicate_to(target.into_raw_fd(), false) });
+	assert!(target.as_raw_fd() == target_raw);
+	assert!(let Ok(false) = target.get_close_on_exec());
+	let mut buf = Vec::new();
+	assert!(let Ok(0) = target.read_to_end(&mut buf));
+
+	let_assert!(Ok(mut target) = unsafe { fd.duplicate_to(target.into_raw_fd(), true) });
+	assert!(target.as_raw_fd() == target_raw);
+	assert!(let Ok(true) = target.get_close_on_exec());
+	assert!(let Ok(5) = target.read_to_end(&mut buf));
+	assert!(buf == b"hello");
+
+	let_assert!(Err(e) = unsafe { fd.duplicate_to(fd.as_raw_fd(), true) });
+	assert!(e.kind() == std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn file_type() {
+	use crate::FileType;
+	use std::os::unix::net::UnixStream;
+
+	assert!(let Ok(FileType::Regular) = temp_file().file_type());
+	let_assert!(Ok(metadata) = temp_file().metadata());
+	assert!(metadata.is_file());
+
+	let_assert!(Ok(dir) = std::fs::File::open("/"));
+	assert!(let Ok(FileType::Directory) = FileDesc::new(dir.into()).file_type());
+
+	let_assert!(Ok(null) = std::fs::File::open("/dev/null"));
+	assert!(let Ok(FileType::CharDevice) = FileDesc::new(null.into()).file_type());
+
+	let_assert!(Ok((socket, _)) = UnixStream::pair());
+	assert!(let Ok(FileType::Socket) = FileDesc::new(socket.into()).file_type());
+
+	let mut fds = [0; 2];
+	assert!(unsafe { libc::pipe(fds.as_mut_ptr()) } == 0);
+	let read = unsafe { FileDesc::from_raw_fd(fds[0]) };
+	let _write = unsafe { FileDesc::from_raw_fd(fds[1]) };
+	assert!(let Ok(FileType::Fifo) = read.file_type());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn file_type_anon_inode() {
+	use crate::FileType;
+
+	let eventfd = unsafe { FileDesc::from_raw_fd(libc::eventfd(0, libc::EFD_CLOEXEC)) };
+	assert!(let Ok(FileType::EventFd) = eventfd.file_type());
+
+	let memfd = unsafe { FileDesc::from_raw_fd(libc::memfd_create(c"test".as_ptr(), libc::MFD_CLOEXEC)) };
+	assert!(let Ok(FileType::MemFd) = memfd.file_type());
+}
+
+#[test]
+fn same_inode_and_open_file() {
+	let a = temp_file();
+	let b = temp_file();
+	let_assert!(Ok(dup) = a.duplicate());
+	assert!(let Ok(true) = a.same_inode(&dup));
+	assert!(let Ok(false) = a.same_inode(&b));
+	assert!(let Ok(false) = a.same_open_file(&b));
+
+	let mut set = std::collections::HashSet::new();
+	assert!(set.insert(a.inode_key().unwrap()));
+	assert!(set.insert(b.inode_key().unwrap()));
+	assert!(!set.insert(dup.inode_key().unwrap()));
+
+	// Opening the same file twice gives the same inode but a different open file description.
+	let_assert!(Ok(first) = std::fs::File::open("/dev/null"));
+	let_assert!(Ok(second) = std::fs::File::open("/dev/null"));
+	let first = FileDesc::new(first.into());
+	let second = FileDesc::new(second.into());
+	assert!(let Ok(true) = first.same_inode(&second));
+
+	#[cfg(target_os = "linux")]
+	{
+		// kcmp() may be restricted in some environments.
+		if let Ok(same) = a.same_open_file(&dup) {
+			assert!(same);
+			assert!(let Ok(false) = first.same_open_file(&second));
+		}
+	}
+}
+
+#[test]
+fn try_from_raw_fd() {
+	use crate::{AccessMode, FileType, WrapErrorKind, WrapOptions};
+
+	let_assert!(Err(e) = unsafe { FileDesc::try_from_raw_fd(-1, &WrapOptions::new()) });
+	assert!(let WrapErrorKind::Negative = e.kind());
+
+	let_assert!(Err(e) = unsafe { FileDesc::try_from_raw_fd(2, &WrapOptions::new()) });
+	assert!(let WrapErrorKind::Stdio = e.kind());
+
+	// Find a file descriptor number that is not open.
+	let closed_raw = temp_file().duplicate_at_least(10_000).unwrap().as_raw_fd();
+	let_assert!(Err(e) = unsafe { FileDesc::try_from_raw_fd(closed_raw, &WrapOptions::new().allow_stdio(true)) });
+	assert!(e.fd() == closed_raw);
+	let_assert!(WrapErrorKind::NotOpen = e.kind());
+
+	let_assert!(Ok(null) = std::fs::File::open("/dev/null"));
+	let raw = FileDesc::new(null.into()).into_raw_fd();
+
+	let options = WrapOptions::new().file_type(FileType::Socket);
+	let_assert!(Err(e) = unsafe { FileDesc::try_from_raw_fd(raw, &options) });
+	let_assert!(WrapErrorKind::FileType { expected: FileType::Socket, actual: FileType::CharDevice } = e.kind());
+
+	let options = WrapOptions::new().access_mode(AccessMode::WriteOnly);
+	let_assert!(Err(e) = unsafe { FileDesc::try_from_raw_fd(raw, &options) });
+	let_assert!(WrapErrorKind::AccessMode { expected: AccessMode::WriteOnly, actual: AccessMode::ReadOnly } = e.kind());
+
+	// Rejected file descriptors are not closed.
+	let options = WrapOptions::new().file_type(FileType::CharDevice).access_mode(AccessMode::ReadOnly);
+	let_assert!(Ok(fd) = unsafe { FileDesc::try_from_raw_fd(raw, &options) });
+	assert!(fd.as_raw_fd() == raw);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn try_from_owned_fd() {
+	use crate::{FileType, MemfdFlags, WrapErrorKind, WrapOptions};
+
+	// A memory file satisfies both the special and the regular file type.
+	let_assert!(Ok(memfd) = FileDesc::memfd("test", MemfdFlags::new()));
+	let options = WrapOptions::new().file_type(FileType::Regular);
+	let_assert!(Ok(memfd) = FileDesc::try_from_owned_fd(memfd.into_fd(), &options));
+	let options = WrapOptions::new().file_type(FileType::MemFd);
+	let_assert!(Ok(memfd) = FileDesc::try_from_owned_fd(memfd.into_fd(), &options));
+
+	let options = WrapOptions::new().file_type(FileType::Socket);
+	let_assert!(Err(e) = FileDesc::try_from_owned_fd(memfd.into_fd(), &options));
+	let_assert!(WrapErrorKind::FileType { expected: FileType::Socket, actual: FileType::MemFd } = e.kind());
+}
+
+#[test]
+fn close() {
+	let fd = temp_file();
+	assert!(let Ok(()) = fd.write_all_at(b"hello", 0));
+	assert!(let Ok(()) = fd.sync_data());
+	assert!(let Ok(()) = fd.sync_and_close());
+
+	let_assert!(Ok((a, _b)) = std::os::unix::net::UnixStream::pair());
+	assert!(let Ok(()) = FileDesc::new(a.into()).close());
+}
+
+#[test]
+fn pipe() {
+	use crate::PipeFlags;
+	use std::io::{Read, Write};
+
+	let_assert!(Ok((mut reader, mut writer)) = FileDesc::pipe(PipeFlags::new()));
+	assert!(let Ok(true) = reader.as_file_desc().get_close_on_exec());
+	assert!(let Ok(true) = writer.as_file_desc().get_close_on_exec());
+	assert!(let Ok(false) = reader.as_file_desc().get_nonblocking());
+
+	assert!(let Ok(()) = writer.write_all(b"hello"));
+	drop(writer);
+	let mut buf = Vec::new();
+	assert!(let Ok(5) = reader.read_to_end(&mut buf));
+	assert!(buf == b"hello");
+
+	let_assert!(Ok((mut reader, writer)) = FileDesc::pipe(PipeFlags::new().nonblocking(true)));
+	assert!(let Ok(true) = writer.as_file_desc().get_nonblocking());
+	let_assert!(Err(e) = reader.read(&mut [0; 8]));
+	assert!(e.kind() == std::io::ErrorKind::WouldBlock);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn pipe_capacity() {
+	use crate::PipeFlags;
+
+	let_assert!(Ok((reader, writer)) = FileDesc::pipe(PipeFlags::new().direct(true)));
+	let_assert!(Ok(capacity) = writer.set_capacity(2 * 4096));
+	assert!(capacity >= 2 * 4096);
+	assert!(reader.capacity().ok() == Some(capacity));
+}
+
+#[test]
+fn socketpair() {
+	use crate::{SocketFlags, SocketType};
+	use std::io::{Read, Write};
+	use std::os::unix::net::{UnixDatagram, UnixStream};
+
+	let_assert!(Ok((a, b)) = FileDesc::socketpair(SocketType::Stream, SocketFlags::new()));
+	assert!(let Ok(true) = a.get_close_on_exec());
+	assert!(let Ok(true) = b.get_close_on_exec());
+	assert!(let Ok(false) = a.get_nonblocking());
+	let mut a = UnixStream::from(a);
+	let mut b = UnixStream::from(b);
+	assert!(let Ok(()) = a.write_all(b"hello"));
+	let mut buf = [0; 5];
+	assert!(let Ok(()) = b.read_exact(&mut buf));
+	assert!(&buf == b"hello");
+
+	let_assert!(Ok((a, b)) = FileDesc::socketpair(SocketType::Datagram, SocketFlags::new().nonblocking(true)));
+	assert!(let Ok(true) = b.get_nonblocking());
+	let a = UnixDatagram::from(a);
+	let b = UnixDatagram::from(b);
+	assert!(let Ok(3) = a.send(b"foo"));
+	assert!(let Ok(3) = a.send(b"bar"));
+	assert!(let Ok(3) = b.recv(&mut [0; 8]));
+	assert!(let Ok(3) = b.recv(&mut [0; 8]));
+	let_assert!(Err(e) = b.recv(&mut [0; 8]));
+	assert!(e.kind() == std::io::ErrorKind::WouldBlock);
+
+	assert!(let Ok(_) = FileDesc::socketpair(SocketType::SeqPacket, SocketFlags::new()));
+}
+
+#[test]
+fn send_recv_fds() {
+	use crate::{recv_fds, send_fds, SocketFlags, SocketType};
+	use std::os::unix::io::AsFd;
+
+	let_assert!(Ok((a, b)) = FileDesc::socketpair(SocketType::Stream, SocketFlags::new()));
+	let file = temp_file();
+	assert!(let Ok(()) = file.write_all_at(b"hello", 0));
+	let_assert!(Ok((reader, _writer)) = FileDesc::pipe(crate::PipeFlags::new()));
+
+	assert!(let Ok(4) = send_fds(&a, b"data", &[file.as_fd(), reader.as_fd()]));
+	let mut buf = [0; 16];
+	let_assert!(Ok((4, fds)) = recv_fds(&b, &mut buf, 4));
+	assert!(&buf[..4] == b"data");
+	assert!(fds.len() == 2);
+	assert!(let Ok(true) = fds[0].get_close_on_exec());
+	assert!(let Ok(true) = fds[0].same_inode(&file));
+	assert!(let Ok(true) = fds[1].same_inode(reader.as_file_desc()));
+
+	let mut contents = [0; 5];
+	assert!(let Ok(()) = fds[0].read_exact_at(&mut contents, 0));
+	assert!(&contents == b"hello");
+
+	// Receiving without room for the file descriptors is reported as an error.
+	// The control buffer may have room for more file descriptors than requested,
+	// due to alignment and the space reserved for credentials, so send a lot more.
+	assert!(let Ok(4) = send_fds(&a, b"data", &[file.as_fd(); 32]));
+	let_assert!(Err(e) = recv_fds(&b, &mut buf, 1));
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+
+	assert!(let Ok(4) = send_fds(&a, b"data", &[]));
+	let_assert!(Ok((4, fds)) = recv_fds(&b, &mut buf, 1));
+	assert!(fds.is_empty());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn peer_credentials() {
+	use crate::{recv_fds_with_credentials, send_fds, SocketFlags, SocketType};
+
+	let_assert!(Ok((a, b)) = FileDesc::socketpair(SocketType::SeqPacket, SocketFlags::new()));
+	let_assert!(Ok(credentials) = a.peer_credentials());
+	assert!(credentials.pid == std::process::id() as libc::pid_t);
+	assert!(credentials.uid == unsafe { libc::getuid() });
+	assert!(credentials.gid == unsafe { libc::getgid() });
+
+	// SO_PEERPIDFD requires Linux 6.5.
+	if let Ok(pidfd) = a.peer_pidfd() {
+		assert!(let Ok(true) = pidfd.get_close_on_exec());
+		assert!(let Ok(crate::FileType::PidFd) = pidfd.file_type());
+	}
+
+	let file = temp_file();
+	assert!(let Ok(()) = b.set_pass_credentials(true));
+	assert!(let Ok(4) = send_fds(&a, b"data", &[file.as_fd()]));
+	let mut buf = [0; 8];
+	let_assert!(Ok((4, fds, Some(received))) = recv_fds_with_credentials(&b, &mut buf, 1));
+	assert!(fds.len() == 1);
+	assert!(received == credentials);
+
+	// Plain recv_fds() still works when the kernel attaches credentials.
+	assert!(let Ok(4) = send_fds(&a, b"data", &[file.as_fd()]));
+	let_assert!(Ok((4, fds)) = crate::recv_fds(&b, &mut buf, 1));
+	assert!(fds.len() == 1);
+	assert!(let Ok(4) = send_fds(&a, b"data", &[]));
+	let_assert!(Ok((4, fds)) = crate::recv_fds(&b, &mut buf, 0));
+	assert!(fds.is_empty());
+}
+
+#[test]
+fn open() {
+	use crate::{AccessMode, OpenFlags};
+
+	let tmp = TempDir::new();
+	let_assert!(Ok(dir) = FileDesc::open(tmp.path(), OpenFlags::new().directory(true)));
+	assert!(let Ok(true) = dir.get_close_on_exec());
+
+	let_assert!(Ok(file) = dir.open_at("file", OpenFlags::new().write(true).create_new(true).mode(0o600)));
+	assert!(let Ok(AccessMode::ReadWrite) = file.access_mode());
+	assert!(let Ok(true) = file.get_close_on_exec());
+	assert!(let Ok(()) = file.write_all_at(b"hello", 0));
+	let_assert!(Err(e) = dir.open_at("file", OpenFlags::new().create_new(true)));
+	assert!(e.kind() == std::io::ErrorKind::AlreadyExists);
+
+	let_assert!(Ok(file) = FileDesc::open(tmp.path().join("file"), OpenFlags::new()));
+	assert!(let Ok(AccessMode::ReadOnly) = file.access_mode());
+	let mut buf = [0; 5];
+	assert!(let Ok(()) = file.read_exact_at(&mut buf, 0));
+	assert!(&buf == b"hello");
+
+	let_assert!(Err(e) = dir.open_at("file", OpenFlags::new().directory(true)));
+	assert!(e.raw_os_error() == Some(libc::ENOTDIR));
+
+	assert!(let Ok(()) = std::os::unix::fs::symlink("file", tmp.path().join("link")));
+	let_assert!(Err(e) = dir.open_at("link", OpenFlags::new().nofollow(true)));
+	assert!(e.raw_os_error() == Some(libc::ELOOP));
+
+	#[cfg(target_os = "linux")]
+	{
+		let_assert!(Ok(link) = dir.open_at("link", OpenFlags::new().path(true).nofollow(true)));
+		assert!(let Ok(AccessMode::Path) = link.access_mode());
+		assert!(let Ok(crate::FileType::Symlink) = link.file_type());
+
+		// Clearing O_TMPFILE does not clear O_DIRECTORY, and the other way around.
+		let_assert!(Err(e) = dir.open_at("file", OpenFlags::new().directory(true).tmpfile(false)));
+		assert!(e.raw_os_error() == Some(libc::ENOTDIR));
+		let_assert!(Ok(file) = dir.open_at(".", OpenFlags::new().write(true).tmpfile(true).directory(false)));
+		assert!(let Ok(crate::FileType::Regular) = file.file_type());
+	}
+}
+
+#[test]
+fn dir() {
+	use crate::{AccessCheck, Dir, FileType, OpenFlags, RenameFlags};
+
+	let tmp = TempDir::new();
+	let_assert!(Ok(dir) = Dir::open(tmp.path()));
+	assert!(let Ok(()) = dir.create_dir("sub", 0o755));
+	let_assert!(Ok(sub) = dir.open_dir("sub"));
+	let_assert!(Ok(file) = sub.open_file("a", OpenFlags::new().write(true).create(true)));
+	assert!(let Ok(()) = file.write_all_at(b"hello", 0));
+
+	let_assert!(Ok(stat) = dir.stat("sub/a", true));
+	assert!(stat.file_type() == FileType::Regular);
+	assert!(stat.len() == 5);
+	assert!(stat.inode_key() == file.inode_key().unwrap());
+	let_assert!(Ok(file_stat) = file.stat());
+	assert!(file_stat.inode_key() == stat.inode_key());
+	assert!(file_stat.len() == 5);
+	assert!(let Ok(true) = dir.access("sub/a", AccessCheck::new().read(true)));
+
+	assert!(let Ok(()) = sub.hard_link("a", &dir, "b"));
+	assert!(dir.stat("b", true).unwrap().nlink() == 2);
+	assert!(let Ok(()) = dir.symlink("sub/a", "link"));
+	assert!(dir.read_link("link").ok() == Some("sub/a".into()));
+	assert!(dir.stat("link", false).unwrap().file_type() == FileType::Symlink);
+
+	// Absolute paths are rejected.
+	let_assert!(Err(e) = dir.stat(tmp.path().join("b"), true));
+	assert!(e.kind() == std::io::ErrorKind::InvalidInput);
+
+	let_assert!(Err(e) = dir.rename("b", &sub, "a", RenameFlags::new().no_replace(true)));
+	assert!(e.kind() == std::io::ErrorKind::AlreadyExists);
+	assert!(let Ok(()) = dir.rename("b", &sub, "c", RenameFlags::new()));
+	assert!(let Ok(()) = sub.remove_file("a"));
+	assert!(let Ok(()) = sub.remove_file("c"));
+	assert!(let Ok(()) = dir.remove_dir("sub"));
+	let_assert!(Err(e) = dir.access("sub", AccessCheck::new()));
+	assert!(e.kind() == std::io::ErrorKind::NotFound);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn dir_rename_exchange() {
+	use crate::{Dir, RenameFlags};
+
+	let tmp = TempDir::new();
+	let_assert!(Ok(dir) = Dir::open(tmp.path()));
+	assert!(let Ok(()) = dir.symlink("1", "a"));
+	assert!(let Ok(()) = dir.symlink("2", "b"));
+	assert!(let Ok(()) = dir.rename("a", &dir, "b", RenameFlags::new().exchange(true)));
+	assert!(dir.read_link("a").ok() == Some("2".into()));
+	assert!(dir.read_link("b").ok() == Some("1".into()));
+}
+
+/// Create a directory tree with symbolic links that try to escape it.
+fn escape_tree() -> (TempDir, crate::Dir) {
+	let tmp = TempDir::new();
+	let_assert!(Ok(outer) = crate::Dir::open(tmp.path()));
+	assert!(let Ok(()) = outer.create_dir("root", 0o755));
+	assert!(let Ok(()) = outer.open_file("secret", crate::OpenFlags::new().write(true).create(true)).map(drop));
+	let_assert!(Ok(root) = outer.open_dir("root"));
+	assert!(let Ok(()) = root.create_dir("sub", 0o755));
+	assert!(let Ok(()) = root.open_file("sub/file", crate::OpenFlags::new().write(true).create(true)).map(drop));
+	assert!(let Ok(()) = root.open_file("secret", crate::OpenFlags::new().write(true).create(true)).map(drop));
+	assert!(let Ok(()) = root.symlink("../secret", "up"));
+	assert!(let Ok(()) = root.symlink("sub/../../secret", "up_nested"));
+	assert!(let Ok(()) = root.symlink(tmp.path().join("secret"), "absolute"));
+	assert!(let Ok(()) = root.symlink("/secret", "rooted"));
+	assert!(let Ok(()) = root.symlink("file", "sub/inner"));
+	(tmp, root)
+}
+
+/// Function to open a path relative to a directory with restrictions on path resolution.
+type Resolver = fn(&crate::Dir, &str, crate::ResolveFlags) -> std::io::Result<FileDesc>;
+
+/// Get functions to resolve a path with `openat2()` (if available) and with the userspace fallback.
+fn resolvers() -> [Resolver; 2] {
+	use crate::OpenFlags;
+	[
+		|root, path, resolve| root.open_resolve(path, OpenFlags::new(), resolve),
+		|root, path, resolve| crate::resolve_in_userspace(root.as_file_desc(), path.as_ref(), OpenFlags::new(), resolve),
+	]
+}
+
+#[test]
+fn open_resolve_beneath() {
+	use crate::{OpenFlags, ResolveFlags};
+
+	let (tmp, root) = escape_tree();
+	let outer_secret = root.as_file_desc().open_at("../secret", OpenFlags::new()).unwrap();
+	let beneath = ResolveFlags::new().beneath(true);
+	for resolve in resolvers() {
+		let_assert!(Ok(_) = resolve(&root, "sub/file", beneath));
+		let_assert!(Ok(_) = resolve(&root, "sub/inner", beneath));
+		let_assert!(Ok(_) = resolve(&root, "sub/../secret", beneath));
+		for path in ["../secret", "sub/../../secret", "up", "up_nested", "absolute", "rooted"] {
+			let_assert!(Err(e) = resolve(&root, path, beneath), "{path} escaped");
+			assert!(e.raw_os_error() == Some(libc::EXDEV), "{path}");
+		}
+		let_assert!(Err(e) = resolve(&root, &tmp.path().join("secret").to_string_lossy(), beneath));
+		assert!(e.raw_os_error() == Some(libc::EXDEV));
+
+		let_assert!(Err(e) = resolve(&root, "sub/inner", ResolveFlags::new().no_symlinks(true)));
+		assert!(e.raw_os_error() == Some(libc::ELOOP));
+
+		// Without restrictions, the links do escape.
+		let_assert!(Ok(fd) = resolve(&root, "up", ResolveFlags::new()));
+		assert!(let Ok(true) = fd.same_inode(&outer_secret));
+	}
+
+	// O_DIRECTORY is part of O_TMPFILE, but opening a directory must not pass a creation mode to openat2().
+	let directory = OpenFlags::new().directory(true);
+	let_assert!(Ok(fd) = root.open_resolve("sub", directory, beneath));
+	assert!(let Ok(crate::FileType::Directory) = fd.file_type());
+	let_assert!(Ok(fd) = crate::resolve_in_userspace(root.as_file_desc(), "sub".as_ref(), directory, beneath));
+	assert!(let Ok(crate::FileType::Directory) = fd.file_type());
+}
+
+#[test]
+fn open_resolve_in_root() {
+	use crate::{OpenFlags, ResolveFlags};
+
+	let (_tmp, root) = escape_tree();
+	let inner_secret = root.open_file("secret", OpenFlags::new()).unwrap();
+	let in_root = ResolveFlags::new().in_root(true);
+	for resolve in resolvers() {
+		// Every attempt to escape ends up at the secret in the root instead.
+		for path in ["../secret", "/secret", "/../../secret", "sub/../../secret", "up", "up_nested", "rooted"] {
+			let_assert!(Ok(fd) = resolve(&root, path, in_root), "{path}");
+			assert!(let Ok(true) = fd.same_inode(&inner_secret), "{path}");
+		}
+
+		// The absolute link is resolved inside the root, where it does not exist.
+		let_assert!(Err(e) = resolve(&root, "absolute", in_root));
+		assert!(e.kind() == std::io::ErrorKind::NotFound);
+	}
+}
+
+#[test]
+fn read_dir() {
+	use crate::{Dir, FileType, OpenFlags, ReadDir};
+
+	let tmp = TempDir::new();
+	let_assert!(Ok(dir) = Dir::open(tmp.path()));
+	assert!(let Ok(()) = dir.create_dir("sub", 0o755));
+	assert!(let Ok(()) = dir.symlink("sub", "link"));
+	let_assert!(Ok(file) = dir.open_file("file", OpenFlags::new().write(true).create(true)));
+
+	let_assert!(Ok(mut read_dir) = dir.read_dir());
+	let_assert!(Ok(mut entries) = read_dir.by_ref().collect::<Result<Vec<_>, _>>());
+	entries.sort_by(|a, b| a.file_name().cmp(b.file_name()));
+	let names: Vec<_> = entries.iter().map(|entry| entry.file_name().to_str().unwrap()).collect();
+	assert!(names == ["file", "link", "sub"]);
+	assert!(entries[0].ino() == file.inode_key().unwrap().ino);
+	for (entry, expected) in entries.iter().zip([FileType::Regular, FileType::Symlink, FileType::Directory]) {
+		if let Some(file_type) = entry.file_type() {
+			assert!(file_type == expected);
+		}
+	}
+
+	assert!(read_dir.next().is_none());
+	assert!(let Ok(()) = read_dir.rewind());
+	assert!(read_dir.count() == 3);
+
+	// Iterating over a duplicate rewinds the shared offset first.
+	let_assert!(Ok(dup) = dir.as_file_desc().duplicate());
+	let_assert!(Ok(read_dir) = ReadDir::new(dup));
+	assert!(read_dir.count() == 3);
+	let_assert!(Ok(dup) = dir.as_file_desc().duplicate());
+	let_assert!(Ok(read_dir) = ReadDir::new(dup));
+	assert!(read_dir.count() == 3);
+}
+
+/// Create a directory tree for testing the walker.
+fn walk_tree() -> (TempDir, crate::Dir) {
+	use crate::{Dir, OpenFlags};
+
+	let tmp = TempDir::new();
+	let_assert!(Ok(dir) = Dir::open(tmp.path()));
+	for path in ["a", "a/b", "a/b/c", "a/b/c/d", "e"] {
+		assert!(let Ok(()) = dir.create_dir(path, 0o755));
+	}
+	for path in ["f", "a/g", "a/b/h", "a/b/c/d/i"] {
+		assert!(let Ok(_) = dir.open_file(path, OpenFlags::new().write(true).create(true)));
+	}
+	// Make sure the walker does not follow symbolic links.
+	assert!(let Ok(()) = dir.symlink("..", "a/b/up"));
+	(tmp, dir)
+}
+
+#[test]
+fn walk() {
+	use crate::{FileType, WalkOptions};
+
+	let (_tmp, dir) = walk_tree();
+	for max_open_fds in [1, 2, 32] {
+		let mut paths = Vec::new();
+		let options = WalkOptions::new().max_open_fds(max_open_fds);
+		assert!(let Ok(()) = dir.walk(options, |_, entry| {
+			paths.push((entry.path().to_str().unwrap().to_owned(), entry.depth(), entry.file_type()));
+			Ok(true)
+		}));
+		paths.sort_by(|a, b| a.0.cmp(&b.0));
+		assert!(paths == [
+			("a".to_owned(), 1, FileType::Directory),
+			("a/b".to_owned(), 2, FileType::Directory),
+			("a/b/c".to_owned(), 3, FileType::Directory),
+			("a/b/c/d".to_owned(), 4, FileType::Directory),
+			("a/b/c/d/i".to_owned(), 5, FileType::Regular),
+			("a/b/h".to_owned(), 3, FileType::Regular),
+			("a/b/up".to_owned(), 3, FileType::Symlink),
+			("a/g".to_owned(), 2, FileType::Regular),
+			("e".to_owned(), 1, FileType::Directory),
+			("f".to_owned(), 1, FileType::Regular),
+		]);
+	}
+
+	let mut count = 0;
+	assert!(let Ok(()) = dir.walk(WalkOptions::new().max_depth(Some(2)), |_, entry| {
+		assert!(entry.depth() <= 2);
+		count += 1;
+		Ok(true)
+	}));
+	assert!(count == 5);
+
+	let mut count = 0;
+	assert!(let Ok(()) = dir.walk(WalkOptions::new().max_depth(Some(1)), |_, entry| {
+		assert!(entry.depth() == 1);
+		count += 1;
+		Ok(true)
+	}));
+	assert!(count == 3);
+
+	// A maximum depth of zero visits nothing.
+	assert!(let Ok(()) = dir.walk(WalkOptions::new().max_depth(Some(0)), |_, entry| {
+		panic!("unexpected entry: {:?}", entry.path());
+	}));
+
+	// The visitor can skip directories.
+	let mut count = 0;
+	assert!(let Ok(()) = dir.walk(WalkOptions::new(), |_, entry| {
+		count += 1;
+		Ok(entry.file_name() != "a")
+	}));
+	assert!(count == 3);
+}
+
+#[test]
+fn walk_same_file_system() {
+	use crate::{Dir, WalkOptions};
+	use std::os::unix::fs::MetadataExt;
+
+	// Use /proc as mount point, if it is on a different file system than the root directory.
+	let (Ok(root), Ok(proc)) = (std::fs::metadata("/"), std::fs::metadata("/proc")) else {
+		return;
+	};
+	if root.dev() == proc.dev() {
+		return;
+	}
+
+	// Only descend into /proc, so that the walk does not depend on the rest of the file system.
+	let_assert!(Ok(dir) = Dir::open("/"));
+	let walk_proc = |options: WalkOptions| {
+		let mut count = 0;
+		assert!(let Ok(()) = dir.walk(options.max_depth(Some(2)), |_, entry| {
+			if entry.depth() == 2 {
+				count += 1;
+			}
+			Ok(entry.file_name() == "proc")
+		}));
+		count
+	};
+	assert!(walk_proc(WalkOptions::new()) > 0);
+	assert!(walk_proc(WalkOptions::new().same_file_system(true)) == 0);
+}
+
+#[test]
+fn remove_tree() {
+	use crate::WalkOptions;
+
+	let (tmp, dir) = walk_tree();
+	let_assert!(Err(e) = dir.remove_tree("a", WalkOptions::new().max_depth(Some(2))));
+	assert!(e.raw_os_error() == Some(libc::ENOTEMPTY));
+	let_assert!(Err(e) = dir.remove_tree("a", WalkOptions::new().max_depth(Some(0))));
+	assert!(e.raw_os_error() == Some(libc::ENOTEMPTY));
+	assert!(let Ok(()) = dir.remove_tree("a", WalkOptions::new().max_open_fds(1)));
+	assert!(let Ok(()) = dir.remove_tree("f", WalkOptions::new()));
+	assert!(let Ok(()) = dir.remove_tree("e", WalkOptions::new()));
+	let_assert!(Ok(read_dir) = dir.read_dir());
+	assert!(read_dir.count() == 0);
+	assert!(tmp.path().exists());
+}
+
+#[test]
+fn tmpfile_persist() {
+	use crate::Dir;
+
+	// Also test the fallback for file systems without O_TMPFILE.
+	for (tmpfile_in, named) in [(FileDesc::tmpfile_in as fn(&Dir) -> _, false), (FileDesc::named_tmpfile_in, true)] {
+		let tmp = TempDir::new();
+		let_assert!(Ok(dir) = Dir::open(tmp.path()));
+		let_assert!(Ok(file) = tmpfile_in(&dir));
+		assert!(let Ok(()) = file.as_file_desc().write_all_at(b"first", 0));
+		assert!(dir.read_dir().unwrap().count() == usize::from(named));
+		let_assert!(Ok(file) = file.persist(&dir, "a", false));
+		assert!(std::fs::read(tmp.path().join("a")).unwrap() == b"first");
+		assert!(dir.stat("a", false).unwrap().inode_key() == file.inode_key().unwrap());
+		assert!(dir.stat("a", false).unwrap().permissions() == 0o600);
+
+		// Without replace, an existing name is not overwritten.
+		let_assert!(Ok(other) = tmpfile_in(&dir));
+		let_assert!(Err(e) = other.persist(&dir, "a", false));
+		assert!(e.kind() == std::io::ErrorKind::AlreadyExists);
+
+		let_assert!(Ok(other) = tmpfile_in(&dir));
+		assert!(let Ok(()) = other.as_file_desc().write_all_at(b"second", 0));
+		let_assert!(Ok(other) = other.persist(&dir, "a", true));
+		assert!(std::fs::read(tmp.path().join("a")).unwrap() == b"second");
+		assert!(dir.stat("a", false).unwrap().inode_key() == other.inode_key().unwrap());
+		assert!(dir.read_dir().unwrap().count() == 1);
+	}
+}
+
+#[test]
+fn atomic_writer() {
+	use crate::{AtomicWriter, Dir};
+	use std::io::Write;
+
+	let tmp = TempDir::new();
+	let_assert!(Ok(dir) = Dir::open(tmp.path()));
+	let_assert!(Ok(mut writer) = AtomicWriter::new(&dir, "config"));
+	assert!(let Ok(()) = writer.write_all(b"first"));
+	let_assert!(Ok(file) = writer.commit());
+	assert!(std::fs::read(tmp.path().join("config")).unwrap() == b"first");
+	assert!(dir.stat("config", false).unwrap().permissions() == 0o644);
+	assert!(dir.stat("config", false).unwrap().inode_key() == file.inode_key().unwrap());
+
+	// The permissions of the existing file are preserved.
+	assert!(let Ok(()) = std::fs::set_permissions(tmp.path().join("config"), std::os::unix::fs::PermissionsExt::from_mode(0o640)));
+	let_assert!(Ok(mut writer) = AtomicWriter::new(&dir, "config"));
+	assert!(let Ok(()) = writer.write_all(b"second"));
+	assert!(let Ok(_) = writer.commit());
+	assert!(std::fs::read(tmp.path().join("config")).unwrap() == b"second");
+	assert!(dir.stat("config", false).unwrap().permissions() == 0o640);
+
+	let_assert!(Ok(mut writer) = AtomicWriter::new(&dir, "config"));
+	writer = writer.mode(0o600);
+	assert!(let Ok(()) = writer.write_all(b"third"));
+	assert!(let Ok(_) = writer.commit());
+	assert!(dir.stat("config", false).unwrap().permissions() == 0o600);
+
+	// Dropping the writer leaves the file untouched.
+	let_assert!(Ok(mut writer) = AtomicWriter::new(&dir, "config"));
+	assert!(let Ok(()) = writer.write_all(b"fourth"));
+	drop(writer);
+	assert!(std::fs::read(tmp.path().join("config")).unwrap() == b"third");
+	assert!(dir.read_dir().unwrap().count() == 1);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn memfd_seals() {
+	use crate::{MemfdFlags, Seals};
+
+	let_assert!(Ok(fd) = FileDesc::memfd("test", MemfdFlags::new().allow_sealing(true)));
+	assert!(let Ok(true) = fd.get_close_on_exec());
+	assert!(let Ok(crate::FileType::MemFd) = fd.file_type());
+	assert!(fd.get_seals().ok() == Some(Seals::new()));
+	assert!(let Ok(()) = fd.write_all_at(b"hello", 0));
+
+	let immutable = Seals::new().shrink(true).grow(true).write(true);
+	let_assert!(Err(e) = fd.require_seals(immutable));
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+	assert!(let Ok(()) = fd.add_seals(immutable.seal(true)));
+	assert!(let Ok(()) = fd.require_seals(immutable));
+	assert!(fd.get_seals().ok() == Some(immutable.seal(true)));
+
+	let_assert!(Err(e) = fd.write_at(b"world", 0));
+	assert!(e.raw_os_error() == Some(libc::EPERM));
+	let_assert!(Err(e) = fd.add_seals(Seals::new().exec(true)));
+	assert!(e.raw_os_error() == Some(libc::EPERM));
+
+	// Without MFD_ALLOW_SEALING, the file is sealed against new seals.
+	let_assert!(Ok(fd) = FileDesc::memfd("test", MemfdFlags::new()));
+	assert!(fd.get_seals().ok() == Some(Seals::new().seal(true)));
+	assert!(let Err(_) = fd.require_seals(immutable));
+}
+
+#[test]
+fn map() {
+	use crate::{Advice, MapFlags, Protection};
+
+	let file = temp_file();
+	assert!(let Ok(()) = file.write_all_at(&[1; 8192], 0));
+	let rw = Protection::new().write(true);
+
+	unsafe {
+		let_assert!(Ok(mut map) = file.map_mut(100..4200, rw, MapFlags::new()));
+		assert!(map.len() == 4100);
+		assert!(std::ptr::eq(map.file_desc(), &file));
+		assert!(let Ok(()) = map.advise(Advice::Sequential));
+		map[0] = 2;
+		map[4099] = 3;
+		assert!(let Ok(()) = map.flush());
+		drop(map);
+
+		let mut buf = [0; 1];
+		assert!(let Ok(()) = file.read_exact_at(&mut buf, 100));
+		assert!(buf == [2]);
+		assert!(let Ok(()) = file.read_exact_at(&mut buf, 4199));
+		assert!(buf == [3]);
+
+		// Writes to a private mapping are not written back to the file.
+		let_assert!(Ok(mut map) = file.map_mut(0..8192, rw, MapFlags::new().private(true)));
+		map[0] = 4;
+		assert!(let Ok(()) = file.read_exact_at(&mut buf, 0));
+		assert!(buf == [1]);
+
+		let_assert!(Ok(map) = file.map(0..4096, Protection::new(), MapFlags::new()));
+		assert!(map[100] == 2);
+		let_assert!(Err(e) = file.map_mut(0..4096, Protection::new(), MapFlags::new()));
+		assert!(e.kind() == std::io::ErrorKind::InvalidInput);
+		let_assert!(Err(e) = file.map(0..4096, rw, MapFlags::new()));
+		assert!(e.kind() == std::io::ErrorKind::InvalidInput);
+		let_assert!(Err(e) = file.map(0..4096, Protection::new().read(false), MapFlags::new()));
+		assert!(e.kind() == std::io::ErrorKind::InvalidInput);
+		let_assert!(Err(e) = file.map_mut(0..4096, rw.read(false), MapFlags::new()));
+		assert!(e.kind() == std::io::ErrorKind::InvalidInput);
+
+		#[cfg(target_os = "linux")]
+		{
+			let mut map = map;
+			assert!(let Ok(()) = map.remap(8192));
+			assert!(map.len() == 8192);
+			assert!(map[4199] == 3);
+		}
+	}
+}
+
+#[test]
+fn map_access_mode() {
+	use crate::{MapFlags, OpenFlags, Protection};
+
+	let tmp = TempDir::new();
+	let path = tmp.path().join("file");
+	assert!(let Ok(()) = std::fs::write(&path, [0; 16]));
+	let_assert!(Ok(file) = FileDesc::open(&path, OpenFlags::new()));
+	let rw = Protection::new().write(true);
+	unsafe {
+		let_assert!(Err(e) = file.map_mut(0..16, rw, MapFlags::new()));
+		assert!(e.kind() == std::io::ErrorKind::PermissionDenied);
+		assert!(let Ok(_) = file.map_mut(0..16, rw, MapFlags::new().private(true)));
+	}
+
+	let_assert!(Ok(file) = FileDesc::open(&path, OpenFlags::new().read(false).write(true)));
+	unsafe {
+		let_assert!(Err(e) = file.map(0..16, Protection::new(), MapFlags::new()));
+		assert!(e.kind() == std::io::ErrorKind::PermissionDenied);
+	}
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn eventfd() {
+	use crate::EventFd;
+
+	let_assert!(Ok(event) = EventFd::new(2, false, false));
+	assert!(let Ok(true) = event.as_file_desc().get_close_on_exec());
+	assert!(let Ok(crate::FileType::EventFd) = event.as_file_desc().file_type());
+	assert!(let Ok(()) = event.signal(3));
+	assert!(let Ok(5) = event.wait());
+	assert!(let Ok(None) = event.try_wait());
+	assert!(let Ok(()) = event.signal(1));
+	assert!(let Ok(Some(1)) = event.try_wait());
+
+	let_assert!(Ok(semaphore) = EventFd::new(2, true, true));
+	assert!(let Ok(1) = semaphore.wait());
+	assert!(let Ok(Some(1)) = semaphore.try_wait());
+	let_assert!(Err(e) = semaphore.wait());
+	assert!(e.kind() == std::io::ErrorKind::WouldBlock);
+
+	// The counter keeps working after a round trip through a plain FileDesc.
+	let_assert!(Ok(fd) = event.as_file_desc().duplicate());
+	let waiter = EventFd::from_file_desc(fd);
+	let thread = std::thread::spawn(move || waiter.wait());
+	assert!(let Ok(()) = event.signal(7));
+	assert!(let Ok(Ok(7)) = thread.join());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn timerfd() {
+	use crate::{Clock, TimerFd};
+	use std::time::Duration;
+
+	let_assert!(Ok(timer) = TimerFd::new(Clock::Monotonic, false));
+	assert!(let Ok(true) = timer.as_file_desc().get_close_on_exec());
+	assert!(let Ok(crate::FileType::TimerFd) = timer.as_file_desc().file_type());
+	assert!(let Ok(None) = timer.remaining());
+	assert!(let Ok(None) = timer.try_wait());
+
+	assert!(let Ok(()) = timer.arm(Duration::from_secs(100), Some(Duration::from_secs(5))));
+	let_assert!(Ok(Some(remaining)) = timer.remaining());
+	assert!(remaining > Duration::from_secs(99));
+	let_assert!(Ok(Some(interval)) = timer.interval());
+	assert!(interval == Duration::from_secs(5));
+	assert!(let Ok(None) = timer.try_wait());
+	assert!(let Ok(()) = timer.disarm());
+	assert!(let Ok(None) = timer.remaining());
+
+	assert!(let Ok(()) = timer.arm(Duration::ZERO, None));
+	assert!(let Ok(1) = timer.wait());
+	assert!(let Ok(None) = timer.interval());
+
+	// A deadline in the past expires immediately.
+	let_assert!(Ok(timer) = TimerFd::new(Clock::Realtime, true));
+	assert!(let Ok(()) = timer.arm_absolute(Duration::from_secs(1), None, true));
+	assert!(let Ok(1) = timer.wait());
+	let_assert!(Err(e) = timer.wait());
+	assert!(e.kind() == std::io::ErrorKind::WouldBlock);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn signalfd() {
+	use crate::{SignalFd, SignalSet};
+
+	// Run in a separate thread, so that the blocked signals do not leak into other tests.
+	let thread = std::thread::spawn(|| {
+		// A failed update of the file descriptor restores the signal mask of the thread.
+		let_assert!(Ok(mask) = SignalSet::from_signals(&[libc::SIGUSR2]));
+		assert!(let Err(_) = SignalFd::from_file_desc(temp_file()).set_mask(&mask));
+		unsafe {
+			let mut blocked: libc::sigset_t = std::mem::zeroed();
+			assert!(libc::pthread_sigmask(libc::SIG_BLOCK, std::ptr::null(), &mut blocked) == 0);
+			assert!(libc::sigismember(&blocked, libc::SIGUSR2) == 0);
+		}
+
+		let_assert!(Ok(mask) = SignalSet::from_signals(&[libc::SIGUSR1]));
+		assert!(mask.contains(libc::SIGUSR1));
+		assert!(!mask.contains(libc::SIGUSR2));
+		let_assert!(Ok(signals) = SignalFd::new(&mask, false));
+		assert!(let Ok(true) = signals.as_file_desc().get_close_on_exec());
+		assert!(let Ok(crate::FileType::SignalFd) = signals.as_file_desc().file_type());
+		assert!(let Ok(None) = signals.try_wait());
+
+		// raise() sends the signal to the calling thread.
+		assert!(unsafe { libc::raise(libc::SIGUSR1) } == 0);
+		let_assert!(Ok(info) = signals.wait());
+		assert!(info.signal() == libc::SIGUSR1);
+		assert!(info.code() == libc::SI_TKILL);
+		assert!(info.pid() == std::process::id());
+		assert!(info.uid() == unsafe { libc::getuid() });
+
+		let mut mask = mask;
+		assert!(let Ok(()) = mask.insert(libc::SIGUSR2));
+		assert!(let Ok(()) = signals.set_mask(&mask));
+		assert!(unsafe { libc::raise(libc::SIGUSR2) } == 0);
+		let_assert!(Ok(Some(info)) = signals.try_wait());
+		assert!(info.signal() == libc::SIGUSR2);
+		assert!(let Ok(None) = signals.try_wait());
+	});
+	assert!(let Ok(()) = thread.join());
+}
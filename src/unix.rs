@@ -4,6 +4,90 @@ use std::os::raw::c_int;
 use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::os::unix::io::{AsFd, BorrowedFd, OwnedFd};
 
+/// Implement the conversions to [`FileDesc`] and the standard file descriptor traits for a type with an `fd: FileDesc` field.
+///
+/// This must be defined before the submodules that use it.
+macro_rules! impl_file_desc_wrapper {
+	($type:ident) => {
+		impl From<$type> for $crate::FileDesc {
+			fn from(value: $type) -> Self {
+				value.fd
+			}
+		}
+
+		impl From<$type> for std::os::unix::io::OwnedFd {
+			fn from(value: $type) -> Self {
+				value.fd.into()
+			}
+		}
+
+		impl std::os::unix::io::AsFd for $type {
+			fn as_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+				self.fd.as_fd()
+			}
+		}
+
+		impl std::os::unix::io::AsRawFd for $type {
+			fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+				self.fd.as_raw_fd()
+			}
+		}
+
+		impl std::os::unix::io::IntoRawFd for $type {
+			fn into_raw_fd(self) -> std::os::unix::io::RawFd {
+				self.fd.into_raw_fd()
+			}
+		}
+	};
+}
+
+mod atomic_write;
+mod dir;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod eventfd;
+mod io;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod memfd;
+mod metadata;
+mod mmap;
+mod open;
+mod pipe;
+mod read_dir;
+mod resolve;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod signalfd;
+mod socket;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod timerfd;
+mod tmpfile;
+mod walk;
+mod wrap;
+
+pub use atomic_write::AtomicWriter;
+pub use dir::{AccessCheck, Dir, RenameFlags};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use eventfd::EventFd;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use memfd::{MemfdFlags, Seals};
+pub use metadata::{FileType, InodeKey, Stat};
+pub use mmap::{Advice, MapFlags, Mmap, MmapMut, Protection};
+pub use open::OpenFlags;
+pub use pipe::{PipeFlags, PipeReader, PipeWriter};
+pub use read_dir::{DirEntry, ReadDir};
+pub use resolve::ResolveFlags;
+#[cfg(test)]
+pub(crate) use resolve::resolve_in_userspace;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use signalfd::{SignalFd, SignalInfo, SignalSet};
+pub use socket::{recv_fds, send_fds, Credentials, SocketFlags, SocketType};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use socket::recv_fds_with_credentials;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use timerfd::{Clock, TimerFd};
+pub use tmpfile::TempFile;
+pub use walk::{WalkEntry, WalkOptions};
+pub use wrap::{WrapError, WrapErrorKind, WrapOptions};
+
 #[derive(Debug)]
 /// Thin wrapper around an open file descriptor.
 ///
@@ -62,11 +146,38 @@ impl FileDesc {
 		}
 	}
 
+	/// Duplicate a file descriptor from an object that has a file descriptor, without setting the `close-on-exec` flag.
+	///
+	/// The new file descriptor will be inherited by child processes when they execute a new program.
+	/// This is intended for deliberately passing a file descriptor to a child process.
+	/// Beware that if other threads may fork and exec at the same time, they will also inherit the file descriptor.
+	///
+	/// The duplicated [`FileDesc`] will be the sole owner of the new file descriptor, but it will share ownership of the underlying kernel object.
+	pub fn duplicate_inheritable_from<T: AsFd>(other: T) -> std::io::Result<Self> {
+		unsafe {
+			let fd = check_ret(libc::dup(other.as_fd().as_raw_fd()))?;
+			Ok(Self::from_raw_fd(fd))
+		}
+	}
+
+	/// Duplicate a raw file descriptor and wrap it in a [`FileDesc`], without setting the `close-on-exec` flag.
+	///
+	/// See [`Self::duplicate_inheritable_from()`] for more details.
+	///
+	/// # Safety
+	/// The file descriptor must be valid,
+	/// and duplicating it must not violate the safety requirements of any object already using the file descriptor.
+	pub unsafe fn duplicate_inheritable_raw_fd(fd: RawFd) -> std::io::Result<Self> {
+		unsafe {
+			Self::duplicate_inheritable_from(BorrowedFd::borrow_raw(fd))
+		}
+	}
+
 	/// Get the file descriptor.
 	///
 	/// This function does not release ownership of the underlying file descriptor.
 	/// The file descriptor will still be closed when the [`FileDesc`] is dropped.
-	pub fn as_fd(&self) -> BorrowedFd {
+	pub fn as_fd(&self) -> BorrowedFd<'_> {
 		self.fd.as_fd()
 	}
 
@@ -94,6 +205,37 @@ impl FileDesc {
 		self.fd.into_raw_fd()
 	}
 
+	/// Close the file descriptor and report any error.
+	///
+	/// Dropping a [`FileDesc`] also closes the file descriptor, but any error is silently ignored.
+	/// Some file systems (like NFS) only report write errors when the file is closed,
+	/// so use this function if you need to know about those errors.
+	///
+	/// The file descriptor is never closed more than once, not even if the call is interrupted by a signal.
+	/// On Linux, the file descriptor is always released, even if an error is returned.
+	/// Retrying the call would risk closing a file descriptor that was opened by another thread in the mean time.
+	pub fn close(self) -> std::io::Result<()> {
+		unsafe {
+			check_ret(libc::close(self.into_raw_fd()))?;
+			Ok(())
+		}
+	}
+
+	/// Flush all data and metadata to the storage device, and then close the file descriptor.
+	///
+	/// See [`Self::sync_all()`] and [`Self::close()`] for more details.
+	/// The file descriptor is closed even if the flush fails.
+	/// In that case, the error from the flush is returned.
+	pub fn sync_and_close(self) -> std::io::Result<()> {
+		match self.sync_all() {
+			Ok(()) => self.close(),
+			Err(e) => {
+				let _ = self.close();
+				Err(e)
+			},
+		}
+	}
+
 	/// Try to duplicate the file descriptor.
 	///
 	/// The duplicated [`FileDesc`] will be the sole owner of the new file descriptor, but it will share ownership of the underlying kernel object.
@@ -104,6 +246,78 @@ impl FileDesc {
 		Self::duplicate_from(self)
 	}
 
+	/// Try to duplicate the file descriptor without setting the `close-on-exec` flag.
+	///
+	/// The new file descriptor will be inherited by child processes when they execute a new program.
+	/// Unlike calling [`Self::set_close_on_exec()`] on a normal duplicate, this does not require a second system call.
+	/// Beware that if other threads may fork and exec at the same time, they will also inherit the file descriptor.
+	///
+	/// The duplicated [`FileDesc`] will be the sole owner of the new file descriptor, but it will share ownership of the underlying kernel object.
+	pub fn duplicate_inheritable(&self) -> std::io::Result<Self> {
+		Self::duplicate_inheritable_from(self)
+	}
+
+	/// Duplicate the file descriptor onto a specific file descriptor number.
+	///
+	/// If `target` is already open, it is closed first, as part of the same atomic operation.
+	/// Any errors from closing the old file descriptor are silently ignored.
+	/// After the call, the returned [`FileDesc`] is the sole owner of `target`,
+	/// and it will close `target` when it is dropped.
+	///
+	/// This is mainly useful to redirect standard I/O streams or to prepare file descriptors
+	/// at fixed numbers for a child process.
+	///
+	/// If `close_on_exec` is true, the new file descriptor will have the `close-on-exec` flag set.
+	/// If the platform supports it, the flag will be set atomically using `dup3()`.
+	/// Otherwise, the library falls back to `dup2()` followed by `fcntl()`.
+	/// If `close_on_exec` is false, the flag is cleared.
+	///
+	/// Duplicating a file descriptor onto itself is an error.
+	///
+	/// # Safety
+	/// If `target` is currently open, it must not be owned or used by any other object,
+	/// since that object would suddenly refer to a different file.
+	/// After the call, `target` must not be closed as long as it is managed by the returned [`FileDesc`].
+	pub unsafe fn duplicate_to(&self, target: RawFd, close_on_exec: bool) -> std::io::Result<Self> {
+		if target == self.as_raw_fd() {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				"can not duplicate a file descriptor onto itself",
+			));
+		}
+
+		#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+		unsafe {
+			let flags = if close_on_exec { libc::O_CLOEXEC } else { 0 };
+			let fd = retry_eintr(|| check_ret(libc::dup3(self.as_raw_fd(), target, flags)))?;
+			Ok(Self::from_raw_fd(fd))
+		}
+
+		#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+		unsafe {
+			let fd = retry_eintr(|| check_ret(libc::dup2(self.as_raw_fd(), target)))?;
+			let fd = Self::from_raw_fd(fd);
+			if close_on_exec {
+				fd.set_close_on_exec(true)?;
+			}
+			Ok(fd)
+		}
+	}
+
+	/// Duplicate the file descriptor to the lowest available file descriptor number that is greater than or equal to `min`.
+	///
+	/// Unlike [`Self::duplicate_to()`], this never closes an open file descriptor.
+	///
+	/// The new file descriptor will have the `close-on-exec` flag set atomically (using `F_DUPFD_CLOEXEC`).
+	///
+	/// The duplicated [`FileDesc`] will be the sole owner of the new file descriptor, but it will share ownership of the underlying kernel object.
+	pub fn duplicate_at_least(&self, min: RawFd) -> std::io::Result<Self> {
+		unsafe {
+			let fd = check_ret(libc::fcntl(self.as_raw_fd(), libc::F_DUPFD_CLOEXEC, min))?;
+			Ok(Self::from_raw_fd(fd))
+		}
+	}
+
 	/// Change the close-on-exec flag of the file descriptor.
 	///
 	/// You should always try to create file descriptors with the close-on-exec flag already set atomically instead of changing it later with this function.
@@ -112,9 +326,12 @@ impl FileDesc {
 	/// You can use this without any race condition to disable the `close-on-exec` flag *after* forking but before executing a new program.
 	pub fn set_close_on_exec(&self, close_on_exec: bool) -> std::io::Result<()> {
 		unsafe {
-			// TODO: Are there platforms where we need to preserve other bits?
-			let arg = if close_on_exec { libc::FD_CLOEXEC } else { 0 };
-			check_ret(libc::fcntl(self.fd.as_raw_fd(), libc::F_SETFD, arg))?;
+			// Preserve any other file descriptor flags the platform may define.
+			let flags = check_ret(libc::fcntl(self.fd.as_raw_fd(), libc::F_GETFD, 0))?;
+			let new_flags = set_bit(flags, libc::FD_CLOEXEC, close_on_exec);
+			if new_flags != flags {
+				check_ret(libc::fcntl(self.fd.as_raw_fd(), libc::F_SETFD, new_flags))?;
+			}
 			Ok(())
 		}
 	}
@@ -126,6 +343,134 @@ impl FileDesc {
 			Ok(ret & libc::FD_CLOEXEC != 0)
 		}
 	}
+
+	/// Get the file status flags of the open file description.
+	///
+	/// Unlike the `close-on-exec` flag, the file status flags are shared by all duplicates of the file descriptor.
+	fn get_status_flags(&self) -> std::io::Result<c_int> {
+		unsafe {
+			check_ret(libc::fcntl(self.fd.as_raw_fd(), libc::F_GETFL, 0))
+		}
+	}
+
+	/// Set or clear a file status flag of the open file description, leaving the other flags untouched.
+	fn set_status_flag(&self, flag: c_int, value: bool) -> std::io::Result<()> {
+		let flags = self.get_status_flags()?;
+		let new_flags = set_bit(flags, flag, value);
+		if new_flags != flags {
+			unsafe {
+				check_ret(libc::fcntl(self.fd.as_raw_fd(), libc::F_SETFL, new_flags))?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Change the non-blocking mode of the file descriptor (`O_NONBLOCK`).
+	///
+	/// Note that the non-blocking mode is a property of the open file description.
+	/// It is shared with all duplicates of the file descriptor, including those in other processes.
+	pub fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+		self.set_status_flag(libc::O_NONBLOCK, nonblocking)
+	}
+
+	/// Check if the file descriptor is in non-blocking mode (`O_NONBLOCK`).
+	pub fn get_nonblocking(&self) -> std::io::Result<bool> {
+		Ok(self.get_status_flags()? & libc::O_NONBLOCK != 0)
+	}
+
+	/// Change the append mode of the file descriptor (`O_APPEND`).
+	///
+	/// In append mode, every write is performed at the end of the file.
+	/// The flag is shared with all duplicates of the file descriptor.
+	pub fn set_append(&self, append: bool) -> std::io::Result<()> {
+		self.set_status_flag(libc::O_APPEND, append)
+	}
+
+	/// Check if the file descriptor is in append mode (`O_APPEND`).
+	pub fn get_append(&self) -> std::io::Result<bool> {
+		Ok(self.get_status_flags()? & libc::O_APPEND != 0)
+	}
+
+	/// Change the direct I/O mode of the file descriptor (`O_DIRECT`).
+	///
+	/// Not all file systems support direct I/O, in which case an error is returned.
+	/// The flag is shared with all duplicates of the file descriptor.
+	#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+	pub fn set_direct(&self, direct: bool) -> std::io::Result<()> {
+		self.set_status_flag(libc::O_DIRECT, direct)
+	}
+
+	/// Check if the file descriptor is in direct I/O mode (`O_DIRECT`).
+	#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+	pub fn get_direct(&self) -> std::io::Result<bool> {
+		Ok(self.get_status_flags()? & libc::O_DIRECT != 0)
+	}
+
+	/// Change the `O_NOATIME` flag of the file descriptor.
+	///
+	/// When set, reading from the file does not update the last access time.
+	/// This requires the caller to own the file or to have the `CAP_FOWNER` capability.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn set_noatime(&self, noatime: bool) -> std::io::Result<()> {
+		self.set_status_flag(libc::O_NOATIME, noatime)
+	}
+
+	/// Check the `O_NOATIME` flag of the file descriptor.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn get_noatime(&self) -> std::io::Result<bool> {
+		Ok(self.get_status_flags()? & libc::O_NOATIME != 0)
+	}
+
+	/// Get the access mode the file was opened with.
+	pub fn access_mode(&self) -> std::io::Result<AccessMode> {
+		let flags = self.get_status_flags()?;
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		if flags & libc::O_PATH != 0 {
+			return Ok(AccessMode::Path);
+		}
+		match flags & libc::O_ACCMODE {
+			libc::O_RDONLY => Ok(AccessMode::ReadOnly),
+			libc::O_WRONLY => Ok(AccessMode::WriteOnly),
+			libc::O_RDWR => Ok(AccessMode::ReadWrite),
+			other => Err(std::io::Error::other(format!("unknown access mode: {other:#o}"))),
+		}
+	}
+}
+
+/// The access mode of an open file.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum AccessMode {
+	/// The file is open for reading only (`O_RDONLY`).
+	ReadOnly,
+
+	/// The file is open for writing only (`O_WRONLY`).
+	WriteOnly,
+
+	/// The file is open for reading and writing (`O_RDWR`).
+	ReadWrite,
+
+	/// The file descriptor only identifies a location in the file system (`O_PATH`).
+	///
+	/// It can not be used for reading or writing.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	Path,
+}
+
+impl AccessMode {
+	/// Check if the access mode allows reading.
+	pub fn is_readable(self) -> bool {
+		matches!(self, Self::ReadOnly | Self::ReadWrite)
+	}
+
+	/// Check if the access mode allows writing.
+	pub fn is_writable(self) -> bool {
+		matches!(self, Self::WriteOnly | Self::ReadWrite)
+	}
+
+	/// Check if the access mode allows all access that is allowed by `other`.
+	pub fn allows(self, other: AccessMode) -> bool {
+		self == other || (self == Self::ReadWrite && matches!(other, Self::ReadOnly | Self::WriteOnly))
+	}
 }
 
 impl AsFd for FileDesc {
@@ -176,10 +521,46 @@ impl IntoRawFd for FileDesc {
 ///
 /// If the return value is -1, [`last_os_error()`](std::io::Error::last_os_error) is returned.
 /// Otherwise, the return value is returned wrapped as [`Ok`].
-fn check_ret(ret: c_int) -> std::io::Result<c_int> {
-	if ret == -1 {
+fn check_ret<T: IsMinusOne>(ret: T) -> std::io::Result<T> {
+	if ret.is_minus_one() {
 		Err(std::io::Error::last_os_error())
 	} else {
 		Ok(ret)
 	}
 }
+
+/// Set or clear a bit in a set of flags.
+fn set_bit(flags: c_int, bit: c_int, value: bool) -> c_int {
+	if value {
+		flags | bit
+	} else {
+		flags & !bit
+	}
+}
+
+/// Repeatedly call a function until it fails with something other than [`std::io::ErrorKind::Interrupted`].
+fn retry_eintr<T, F: FnMut() -> std::io::Result<T>>(mut f: F) -> std::io::Result<T> {
+	loop {
+		match f() {
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			other => return other,
+		}
+	}
+}
+
+/// Integer types that libc functions use to signal an error with the value -1.
+trait IsMinusOne {
+	fn is_minus_one(&self) -> bool;
+}
+
+impl IsMinusOne for c_int {
+	fn is_minus_one(&self) -> bool {
+		*self == -1
+	}
+}
+
+impl IsMinusOne for isize {
+	fn is_minus_one(&self) -> bool {
+		*self == -1
+	}
+}
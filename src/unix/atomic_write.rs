@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+
+use super::{check_ret, Dir, FileDesc, FileType, TempFile};
+
+/// Writer that atomically replaces a file in a directory.
+///
+/// The data is written to a temporary file created with [`FileDesc::tmpfile_in()`].
+/// When the writer is committed with [`Self::commit()`], the temporary file is synced to disk and renamed over the target file,
+/// and the directory is synced to make the rename durable.
+/// Readers of the file see either the old or the new contents, never a partially written file.
+///
+/// If the writer is dropped without committing, the temporary file is removed and the target file is left untouched.
+#[derive(Debug)]
+pub struct AtomicWriter<'a> {
+	dir: &'a Dir,
+	name: PathBuf,
+	file: TempFile,
+	mode: Option<u32>,
+	owner: Option<(Option<u32>, Option<u32>)>,
+	preserve: bool,
+}
+
+impl<'a> AtomicWriter<'a> {
+	/// Create a writer that will replace the file `name` in `dir`.
+	///
+	/// The directory must be opened for reading (not with `O_PATH`), so that it can be synced after the rename.
+	pub fn new(dir: &'a Dir, name: impl AsRef<Path>) -> std::io::Result<Self> {
+		Ok(Self {
+			dir,
+			name: name.as_ref().to_owned(),
+			file: FileDesc::tmpfile_in(dir)?,
+			mode: None,
+			owner: None,
+			preserve: true,
+		})
+	}
+
+	/// Set the permissions of the new file.
+	///
+	/// The permissions are applied with `fchmod()`, so they are not subject to the umask of the process.
+	/// This overrides the permissions preserved from the existing file.
+	pub fn mode(mut self, mode: u32) -> Self {
+		self.mode = Some(mode);
+		self
+	}
+
+	/// Set the owner and group of the new file.
+	///
+	/// A value of `None` leaves the owner or group as it is.
+	/// Changing the owner usually requires elevated privileges.
+	/// This overrides the ownership preserved from the existing file.
+	pub fn owner(mut self, uid: Option<u32>, gid: Option<u32>) -> Self {
+		self.owner = Some((uid, gid));
+		self
+	}
+
+	/// Preserve the permissions and ownership of the existing file (enabled by default).
+	///
+	/// If the target file does not exist or is not a regular file, new files get permissions `0o644`,
+	/// and the owner and group of the calling process.
+	/// Ownership is only preserved if it differs from the ownership of the new file,
+	/// so preserving it only requires privileges when the existing file is owned by someone else.
+	pub fn preserve(mut self, preserve: bool) -> Self {
+		self.preserve = preserve;
+		self
+	}
+
+	/// Get a reference to the file descriptor of the temporary file.
+	pub fn as_file_desc(&self) -> &FileDesc {
+		self.file.as_file_desc()
+	}
+
+	/// Apply the metadata, sync the temporary file, rename it over the target file and sync the directory.
+	///
+	/// Returns the file descriptor of the new file.
+	/// On error, the temporary file is removed.
+	/// If syncing the directory fails, the file has already been replaced, but the rename may not be durable yet.
+	pub fn commit(self) -> std::io::Result<FileDesc> {
+		let fd = self.file.as_file_desc();
+		let existing = match self.dir.stat(&self.name, false) {
+			Ok(stat) if self.preserve && stat.file_type() == FileType::Regular => Some(stat),
+			Ok(_) => None,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+			Err(e) => return Err(e),
+		};
+
+		let mode = self.mode.or(existing.as_ref().map(|stat| stat.permissions())).unwrap_or(0o644);
+		unsafe {
+			check_ret(libc::fchmod(fd.as_raw_fd(), mode as libc::mode_t))?;
+		}
+
+		let owner = match (self.owner, &existing) {
+			(Some(owner), _) => Some(owner),
+			(None, Some(existing)) => {
+				let current = fd.stat()?;
+				let uid = Some(existing.uid()).filter(|&uid| uid != current.uid());
+				let gid = Some(existing.gid()).filter(|&gid| gid != current.gid());
+				Some((uid, gid))
+			},
+			(None, None) => None,
+		};
+		if let Some((uid, gid)) = owner.filter(|&(uid, gid)| uid.is_some() || gid.is_some()) {
+			unsafe {
+				// A value of -1 leaves the owner or group unchanged.
+				let uid = uid.map_or(libc::uid_t::MAX, |uid| uid as libc::uid_t);
+				let gid = gid.map_or(libc::gid_t::MAX, |gid| gid as libc::gid_t);
+				check_ret(libc::fchown(fd.as_raw_fd(), uid, gid))?;
+			}
+		}
+
+		fd.sync_all()?;
+		let fd = self.file.persist(self.dir, &self.name, true)?;
+		self.dir.as_file_desc().sync_all()?;
+		Ok(fd)
+	}
+}
+
+impl std::io::Write for AtomicWriter<'_> {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		let mut fd = self.file.as_file_desc();
+		fd.write(buf)
+	}
+
+	fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+		let mut fd = self.file.as_file_desc();
+		fd.write_vectored(bufs)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
@@ -0,0 +1,320 @@
+use std::ffi::{CStr, OsString};
+use std::os::raw::c_int;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use super::open::path_to_cstring;
+use super::{check_ret, retry_eintr, FileDesc, OpenFlags, Stat};
+
+/// A directory file descriptor used to perform operations relative to the directory.
+///
+/// All functions take paths relative to the directory and use the `*at()` family of system calls.
+/// Absolute paths are rejected with an error of kind [`std::io::ErrorKind::InvalidInput`],
+/// so operations never resolve a path outside of the directory from the root of the file system.
+///
+/// Note that relative paths can still escape the directory with `..` components or symbolic links.
+#[derive(Debug)]
+pub struct Dir {
+	fd: FileDesc,
+}
+
+/// Options for [`Dir::rename()`].
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RenameFlags {
+	no_replace: bool,
+	exchange: bool,
+}
+
+impl RenameFlags {
+	/// Create new rename flags with all options disabled.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Fail if the destination already exists (`RENAME_NOREPLACE`).
+	pub fn no_replace(mut self, no_replace: bool) -> Self {
+		self.no_replace = no_replace;
+		self
+	}
+
+	/// Atomically exchange the source and destination (`RENAME_EXCHANGE`).
+	///
+	/// Both the source and destination must exist.
+	pub fn exchange(mut self, exchange: bool) -> Self {
+		self.exchange = exchange;
+		self
+	}
+}
+
+/// The permissions to check with [`Dir::access()`].
+///
+/// If no permissions are selected, only the existence of the file is checked.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct AccessCheck {
+	read: bool,
+	write: bool,
+	execute: bool,
+}
+
+impl AccessCheck {
+	/// Create a new access check that only checks if the file exists.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Check for read permission.
+	pub fn read(mut self, read: bool) -> Self {
+		self.read = read;
+		self
+	}
+
+	/// Check for write permission.
+	pub fn write(mut self, write: bool) -> Self {
+		self.write = write;
+		self
+	}
+
+	/// Check for execute permission (or search permission for directories).
+	pub fn execute(mut self, execute: bool) -> Self {
+		self.execute = execute;
+		self
+	}
+
+	/// Get the raw mode for `faccessat()`.
+	fn to_raw(self) -> c_int {
+		let mut mode = libc::F_OK;
+		if self.read {
+			mode |= libc::R_OK;
+		}
+		if self.write {
+			mode |= libc::W_OK;
+		}
+		if self.execute {
+			mode |= libc::X_OK;
+		}
+		mode
+	}
+}
+
+impl Dir {
+	/// Wrap a directory file descriptor.
+	///
+	/// This does not check that the file descriptor refers to a directory.
+	/// If it does not, all operations will fail with `ENOTDIR`.
+	pub fn new(fd: FileDesc) -> Self {
+		Self { fd }
+	}
+
+	/// Open a directory.
+	///
+	/// The directory is opened with `O_DIRECTORY` and the `close-on-exec` flag set.
+	pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+		Ok(Self::new(FileDesc::open(path, OpenFlags::new().directory(true))?))
+	}
+
+	/// Get a reference to the wrapped [`FileDesc`].
+	pub fn as_file_desc(&self) -> &FileDesc {
+		&self.fd
+	}
+
+	/// Release the wrapped [`FileDesc`].
+	pub fn into_file_desc(self) -> FileDesc {
+		self.fd
+	}
+
+	/// Open a file relative to the directory, using `openat()`.
+	///
+	/// The new file descriptor will have the `close-on-exec` flag set atomically.
+	pub fn open_file(&self, path: impl AsRef<Path>, flags: OpenFlags) -> std::io::Result<FileDesc> {
+		self.fd.open_at(relative(path.as_ref())?, flags)
+	}
+
+	/// Open a subdirectory relative to the directory.
+	///
+	/// The directory is opened with `O_DIRECTORY` and the `close-on-exec` flag set.
+	pub fn open_dir(&self, path: impl AsRef<Path>) -> std::io::Result<Dir> {
+		Ok(Self::new(self.open_file(path, OpenFlags::new().directory(true))?))
+	}
+
+	/// Create a new directory relative to the directory, using `mkdirat()`.
+	///
+	/// The permissions of the directory are subject to the umask of the process.
+	pub fn create_dir(&self, path: impl AsRef<Path>, mode: u32) -> std::io::Result<()> {
+		let path = path_to_cstring(relative(path.as_ref())?)?;
+		unsafe {
+			check_ret(libc::mkdirat(self.as_raw_fd(), path.as_ptr(), mode as libc::mode_t))?;
+			Ok(())
+		}
+	}
+
+	/// Remove a file relative to the directory, using `unlinkat()`.
+	///
+	/// This can not be used to remove directories, use [`Self::remove_dir()`] for that.
+	pub fn remove_file(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+		self.unlink(path.as_ref(), 0)
+	}
+
+	/// Remove an empty directory relative to the directory, using `unlinkat()` with `AT_REMOVEDIR`.
+	pub fn remove_dir(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+		self.unlink(path.as_ref(), libc::AT_REMOVEDIR)
+	}
+
+	/// Call `unlinkat()` with the given flags.
+	fn unlink(&self, path: &Path, flags: c_int) -> std::io::Result<()> {
+		let path = path_to_cstring(relative(path)?)?;
+		unsafe {
+			check_ret(libc::unlinkat(self.as_raw_fd(), path.as_ptr(), flags))?;
+			Ok(())
+		}
+	}
+
+	/// Rename a file or directory, using `renameat2()`.
+	///
+	/// The source path is relative to `self`, the destination path is relative to `new_dir`.
+	/// Both directories must be on the same file system.
+	///
+	/// On platforms without `renameat2()`, this falls back to `renameat()`.
+	/// In that case, using any of the [`RenameFlags`] results in an error of kind [`std::io::ErrorKind::Unsupported`].
+	pub fn rename(&self, from: impl AsRef<Path>, new_dir: &Dir, to: impl AsRef<Path>, flags: RenameFlags) -> std::io::Result<()> {
+		let from = path_to_cstring(relative(from.as_ref())?)?;
+		let to = path_to_cstring(relative(to.as_ref())?)?;
+
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		{
+			let mut raw_flags = 0;
+			if flags.no_replace {
+				raw_flags |= libc::RENAME_NOREPLACE;
+			}
+			if flags.exchange {
+				raw_flags |= libc::RENAME_EXCHANGE;
+			}
+			if raw_flags != 0 {
+				unsafe {
+					let ret = libc::syscall(
+						libc::SYS_renameat2,
+						self.as_raw_fd(),
+						from.as_ptr(),
+						new_dir.as_raw_fd(),
+						to.as_ptr(),
+						raw_flags,
+					);
+					check_ret(ret as c_int)?;
+					return Ok(());
+				}
+			}
+		}
+
+		#[cfg(not(any(target_os = "linux", target_os = "android")))]
+		if flags.no_replace || flags.exchange {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::Unsupported,
+				"rename flags are not supported on this platform",
+			));
+		}
+
+		unsafe {
+			check_ret(libc::renameat(self.as_raw_fd(), from.as_ptr(), new_dir.as_raw_fd(), to.as_ptr()))?;
+			Ok(())
+		}
+	}
+
+	/// Create a hard link, using `linkat()`.
+	///
+	/// The existing path is relative to `self`, the new path is relative to `new_dir`.
+	/// If the existing path is a symbolic link, the link itself is hard linked, not the target.
+	pub fn hard_link(&self, existing: impl AsRef<Path>, new_dir: &Dir, new: impl AsRef<Path>) -> std::io::Result<()> {
+		let existing = path_to_cstring(relative(existing.as_ref())?)?;
+		let new = path_to_cstring(relative(new.as_ref())?)?;
+		unsafe {
+			check_ret(libc::linkat(self.as_raw_fd(), existing.as_ptr(), new_dir.as_raw_fd(), new.as_ptr(), 0))?;
+			Ok(())
+		}
+	}
+
+	/// Create a symbolic link relative to the directory, using `symlinkat()`.
+	///
+	/// The `target` is stored in the link as-is, and may be an absolute path.
+	/// The `link` path is the location of the new link, relative to the directory.
+	pub fn symlink(&self, target: impl AsRef<Path>, link: impl AsRef<Path>) -> std::io::Result<()> {
+		let target = path_to_cstring(target.as_ref())?;
+		let link = path_to_cstring(relative(link.as_ref())?)?;
+		unsafe {
+			check_ret(libc::symlinkat(target.as_ptr(), self.as_raw_fd(), link.as_ptr()))?;
+			Ok(())
+		}
+	}
+
+	/// Read the target of a symbolic link relative to the directory, using `readlinkat()`.
+	pub fn read_link(&self, path: impl AsRef<Path>) -> std::io::Result<PathBuf> {
+		let path = path_to_cstring(relative(path.as_ref())?)?;
+		read_link_at(self.as_raw_fd(), &path)
+	}
+
+	/// Get the metadata of a file relative to the directory, using `fstatat()`.
+	///
+	/// If `follow_symlinks` is false and the path refers to a symbolic link,
+	/// the metadata of the link itself is returned.
+	pub fn stat(&self, path: impl AsRef<Path>, follow_symlinks: bool) -> std::io::Result<Stat> {
+		let path = path_to_cstring(relative(path.as_ref())?)?;
+		let flags = if follow_symlinks { 0 } else { libc::AT_SYMLINK_NOFOLLOW };
+		unsafe {
+			let mut stat: libc::stat = std::mem::zeroed();
+			check_ret(libc::fstatat(self.as_raw_fd(), path.as_ptr(), &mut stat, flags))?;
+			Ok(Stat::from_raw(stat))
+		}
+	}
+
+	/// Check the permissions of a file relative to the directory, using `faccessat()`.
+	///
+	/// The check uses the real user and group ID of the process, like `access()`.
+	/// Returns `Ok(false)` if the access is denied.
+	/// Other errors, including a file that does not exist, are returned as errors.
+	pub fn access(&self, path: impl AsRef<Path>, check: AccessCheck) -> std::io::Result<bool> {
+		let path = path_to_cstring(relative(path.as_ref())?)?;
+		unsafe {
+			match check_ret(libc::faccessat(self.as_raw_fd(), path.as_ptr(), check.to_raw(), 0)) {
+				Ok(_) => Ok(true),
+				Err(e) if e.raw_os_error() == Some(libc::EACCES) => Ok(false),
+				Err(e) => Err(e),
+			}
+		}
+	}
+}
+
+/// Check that a path is relative.
+fn relative(path: &Path) -> std::io::Result<&Path> {
+	if path.is_absolute() {
+		Err(std::io::Error::new(
+			std::io::ErrorKind::InvalidInput,
+			"absolute paths are not allowed for directory relative operations",
+		))
+	} else {
+		Ok(path)
+	}
+}
+
+/// Read the target of a symbolic link with `readlinkat()`, growing the buffer as needed.
+pub(crate) fn read_link_at(dir: RawFd, path: &CStr) -> std::io::Result<PathBuf> {
+	let mut buffer = Vec::<u8>::with_capacity(256);
+	loop {
+		unsafe {
+			let len = retry_eintr(|| check_ret(libc::readlinkat(dir, path.as_ptr(), buffer.as_mut_ptr().cast(), buffer.capacity())))?;
+			let len = len as usize;
+			if len < buffer.capacity() {
+				buffer.set_len(len);
+				return Ok(PathBuf::from(OsString::from_vec(buffer)));
+			}
+		}
+		// The target may have been truncated, try again with a larger buffer.
+		buffer.reserve(buffer.capacity() * 2);
+	}
+}
+
+impl From<FileDesc> for Dir {
+	fn from(value: FileDesc) -> Self {
+		Self::new(value)
+	}
+}
+
+impl_file_desc_wrapper!(Dir);
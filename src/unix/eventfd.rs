@@ -0,0 +1,86 @@
+use std::os::raw::c_uint;
+
+use super::{check_ret, FileDesc};
+
+/// An event counter that can be used to wake up other threads or processes, created with `eventfd()`.
+///
+/// The counter is incremented by [`Self::signal()`], and read and reset by [`Self::wait()`].
+/// In semaphore mode, reading decrements the counter by one instead of resetting it.
+///
+/// The file descriptor can be polled for readability to wait for events together with other file descriptors.
+#[derive(Debug)]
+pub struct EventFd {
+	fd: FileDesc,
+}
+
+impl EventFd {
+	/// Create a new event counter with an initial value.
+	///
+	/// If `semaphore` is true, the counter is created with `EFD_SEMAPHORE`,
+	/// and [`Self::wait()`] decrements the counter by one instead of resetting it to zero.
+	/// If `nonblocking` is true, the counter is created with `EFD_NONBLOCK`.
+	///
+	/// The new file descriptor will have the `close-on-exec` flag set atomically.
+	pub fn new(initial: u32, semaphore: bool, nonblocking: bool) -> std::io::Result<Self> {
+		let mut flags = libc::EFD_CLOEXEC;
+		if semaphore {
+			flags |= libc::EFD_SEMAPHORE;
+		}
+		if nonblocking {
+			flags |= libc::EFD_NONBLOCK;
+		}
+		unsafe {
+			let fd = check_ret(libc::eventfd(initial as c_uint, flags))?;
+			Ok(Self::from_file_desc(FileDesc::from_raw_fd(fd)))
+		}
+	}
+
+	/// Wrap a [`FileDesc`] that refers to an event counter, for example one received with [`crate::recv_fds()`].
+	///
+	/// This does not check that the file descriptor refers to an event counter.
+	/// If it does not, the other functions will fail or behave unexpectedly.
+	/// You can use [`FileDesc::file_type()`] to check the type first.
+	pub fn from_file_desc(fd: FileDesc) -> Self {
+		Self { fd }
+	}
+
+	/// Get a reference to the wrapped [`FileDesc`].
+	pub fn as_file_desc(&self) -> &FileDesc {
+		&self.fd
+	}
+
+	/// Release the wrapped [`FileDesc`].
+	pub fn into_file_desc(self) -> FileDesc {
+		self.fd
+	}
+
+	/// Add `n` to the counter, waking up waiters.
+	///
+	/// If adding `n` would make the counter exceed `u64::MAX - 1`, this blocks until the counter is read,
+	/// or fails with [`std::io::ErrorKind::WouldBlock`] in non-blocking mode.
+	/// A value of `u64::MAX` is rejected by the kernel with `EINVAL`.
+	pub fn signal(&self, n: u64) -> std::io::Result<()> {
+		super::io::write_record(&self.fd, &n.to_ne_bytes())
+	}
+
+	/// Wait until the counter is non-zero, and read it.
+	///
+	/// Normally, this returns the value of the counter and resets it to zero.
+	/// In semaphore mode, this returns 1 and decrements the counter by one.
+	///
+	/// In non-blocking mode, this fails with [`std::io::ErrorKind::WouldBlock`] if the counter is zero.
+	pub fn wait(&self) -> std::io::Result<u64> {
+		super::io::read_u64(&self.fd)
+	}
+
+	/// Read the counter if it is non-zero, without blocking.
+	///
+	/// Returns `None` if the counter is zero.
+	/// If the file descriptor is in blocking mode, this first checks the counter with `poll()`.
+	/// In that case, the call can still block if another thread or process reads the counter at the same time.
+	pub fn try_wait(&self) -> std::io::Result<Option<u64>> {
+		super::io::try_read_u64(&self.fd)
+	}
+}
+
+impl_file_desc_wrapper!(EventFd);
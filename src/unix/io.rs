@@ -0,0 +1,283 @@
+use std::io::{IoSlice, IoSliceMut, Read, Write};
+use std::os::raw::c_int;
+
+use super::{check_ret, retry_eintr, FileDesc};
+
+/// The maximum number of bytes to pass to a single `read` or `write` call.
+///
+/// Linux and most other platforms reject sizes above `isize::MAX`.
+/// macOS rejects sizes above `c_int::MAX` with `EINVAL`.
+#[cfg(target_vendor = "apple")]
+const MAX_RW_LEN: usize = c_int::MAX as usize - 1;
+#[cfg(not(target_vendor = "apple"))]
+const MAX_RW_LEN: usize = isize::MAX as usize;
+
+/// Get the maximum number of buffers that can be passed to a single `readv` or `writev` call.
+fn max_iov() -> c_int {
+	let ret = unsafe { libc::sysconf(libc::_SC_IOV_MAX) };
+	if ret > 0 {
+		ret.min(c_int::MAX as libc::c_long) as c_int
+	} else {
+		// POSIX guarantees at least 16.
+		16
+	}
+}
+
+/// Convert a file offset to an `off_t`, failing if it does not fit.
+pub(crate) fn to_off_t(offset: u64) -> std::io::Result<libc::off_t> {
+	libc::off_t::try_from(offset).map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "file offset too large"))
+}
+
+/// Read a single record from a file descriptor with one `read()` call, like an event counter or timer value.
+///
+/// File descriptors that produce records never return partial records,
+/// so a short read is reported as an error of kind [`std::io::ErrorKind::UnexpectedEof`].
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn read_record(fd: &FileDesc, buf: &mut [u8]) -> std::io::Result<()> {
+	let read = (&*fd).read(buf)?;
+	if read != buf.len() {
+		return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "short read from file descriptor"));
+	}
+	Ok(())
+}
+
+/// Write a single record to a file descriptor with one `write()` call, like an event counter value.
+///
+/// A short write is reported as an error of kind [`std::io::ErrorKind::WriteZero`].
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn write_record(fd: &FileDesc, buf: &[u8]) -> std::io::Result<()> {
+	let written = (&*fd).write(buf)?;
+	if written != buf.len() {
+		return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "short write to file descriptor"));
+	}
+	Ok(())
+}
+
+/// Read a native endian `u64` from a file descriptor, as used by event counters and timers.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn read_u64(fd: &FileDesc) -> std::io::Result<u64> {
+	let mut buf = [0; 8];
+	read_record(fd, &mut buf)?;
+	Ok(u64::from_ne_bytes(buf))
+}
+
+/// Read a native endian `u64` from a file descriptor if it is readable, without blocking.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn try_read_u64(fd: &FileDesc) -> std::io::Result<Option<u64>> {
+	try_read(fd, read_u64)
+}
+
+/// Call a read function if the file descriptor is readable, without blocking.
+///
+/// For file descriptors in blocking mode, this checks for readability with `poll()` first.
+/// Returns `None` if the file descriptor is not readable.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn try_read<T>(fd: &FileDesc, read: impl FnOnce(&FileDesc) -> std::io::Result<T>) -> std::io::Result<Option<T>> {
+	if !fd.get_nonblocking()? {
+		let mut pollfd = libc::pollfd {
+			fd: fd.as_raw_fd(),
+			events: libc::POLLIN,
+			revents: 0,
+		};
+		let ready = retry_eintr(|| unsafe { check_ret(libc::poll(&mut pollfd, 1, 0)) })?;
+		if ready == 0 {
+			return Ok(None);
+		}
+	}
+	match read(fd) {
+		Ok(value) => Ok(Some(value)),
+		Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+		Err(e) => Err(e),
+	}
+}
+
+impl FileDesc {
+	/// Read from the file descriptor at the given offset with `pread(2)`.
+	///
+	/// This does not use or modify the file position of the file descriptor,
+	/// so it can be used concurrently from multiple threads on a shared [`FileDesc`].
+	///
+	/// The call is retried if it is interrupted by a signal.
+	/// Like [`Read::read`], this may read fewer bytes than requested.
+	pub fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+		let len = buf.len().min(MAX_RW_LEN);
+		let offset = to_off_t(offset)?;
+		retry_eintr(|| unsafe { check_ret(libc::pread(self.as_raw_fd(), buf.as_mut_ptr().cast(), len, offset)) }).map(|n| n as usize)
+	}
+
+	/// Write to the file descriptor at the given offset with `pwrite(2)`.
+	///
+	/// This does not use or modify the file position of the file descriptor,
+	/// so it can be used concurrently from multiple threads on a shared [`FileDesc`].
+	/// Note that on Linux, if the file was opened with `O_APPEND`, the data is appended regardless of the offset.
+	///
+	/// The call is retried if it is interrupted by a signal.
+	/// Like [`Write::write`], this may write fewer bytes than requested.
+	pub fn write_at(&self, buf: &[u8], offset: u64) -> std::io::Result<usize> {
+		let len = buf.len().min(MAX_RW_LEN);
+		let offset = to_off_t(offset)?;
+		retry_eintr(|| unsafe { check_ret(libc::pwrite(self.as_raw_fd(), buf.as_ptr().cast(), len, offset)) }).map(|n| n as usize)
+	}
+
+	/// Read from the file descriptor at the given offset into multiple buffers with `preadv(2)`.
+	///
+	/// See [`Self::read_at()`] for more details.
+	#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+	pub fn read_vectored_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> std::io::Result<usize> {
+		// IoSliceMut is guaranteed to be ABI compatible with `struct iovec`.
+		let count = bufs.len().min(max_iov() as usize) as c_int;
+		let offset = to_off_t(offset)?;
+		retry_eintr(|| unsafe { check_ret(libc::preadv(self.as_raw_fd(), bufs.as_mut_ptr().cast(), count, offset)) }).map(|n| n as usize)
+	}
+
+	/// Write multiple buffers to the file descriptor at the given offset with `pwritev(2)`.
+	///
+	/// See [`Self::write_at()`] for more details.
+	#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+	pub fn write_vectored_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> std::io::Result<usize> {
+		// IoSlice is guaranteed to be ABI compatible with `struct iovec`.
+		let count = bufs.len().min(max_iov() as usize) as c_int;
+		let offset = to_off_t(offset)?;
+		retry_eintr(|| unsafe { check_ret(libc::pwritev(self.as_raw_fd(), bufs.as_ptr().cast(), count, offset)) }).map(|n| n as usize)
+	}
+
+	/// Read the exact number of bytes required to fill the buffer, starting at the given offset.
+	///
+	/// This repeatedly calls [`Self::read_at()`] until the buffer is full.
+	/// If the end of the file is reached first, an error of kind [`std::io::ErrorKind::UnexpectedEof`] is returned.
+	/// In that case, the contents of the buffer are unspecified.
+	///
+	/// This does not use or modify the file position of the file descriptor.
+	pub fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+		while !buf.is_empty() {
+			match self.read_at(buf, offset)? {
+				0 => return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "failed to fill whole buffer")),
+				n => {
+					buf = &mut buf[n..];
+					offset += n as u64;
+				},
+			}
+		}
+		Ok(())
+	}
+
+	/// Write the entire buffer, starting at the given offset.
+	///
+	/// This repeatedly calls [`Self::write_at()`] until all data is written.
+	/// If a write call reports that zero bytes were written, an error of kind [`std::io::ErrorKind::WriteZero`] is returned.
+	///
+	/// This does not use or modify the file position of the file descriptor.
+	pub fn write_all_at(&self, mut buf: &[u8], mut offset: u64) -> std::io::Result<()> {
+		while !buf.is_empty() {
+			match self.write_at(buf, offset)? {
+				0 => return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "failed to write whole buffer")),
+				n => {
+					buf = &buf[n..];
+					offset += n as u64;
+				},
+			}
+		}
+		Ok(())
+	}
+
+	/// Flush all data and metadata of the file to the storage device with `fsync(2)`.
+	pub fn sync_all(&self) -> std::io::Result<()> {
+		retry_eintr(|| unsafe { check_ret(libc::fsync(self.as_raw_fd())) })?;
+		Ok(())
+	}
+
+	/// Flush all data of the file to the storage device with `fdatasync(2)`.
+	///
+	/// Unlike [`Self::sync_all()`], this does not flush metadata that is not needed to read the data back,
+	/// like the modification time.
+	/// On platforms without `fdatasync()`, this is the same as [`Self::sync_all()`].
+	pub fn sync_data(&self) -> std::io::Result<()> {
+		#[cfg(not(target_vendor = "apple"))]
+		retry_eintr(|| unsafe { check_ret(libc::fdatasync(self.as_raw_fd())) })?;
+		#[cfg(target_vendor = "apple")]
+		self.sync_all()?;
+		Ok(())
+	}
+
+	/// Read from the file descriptor with `read(2)`.
+	///
+	/// The call is retried if it is interrupted by a signal.
+	fn read_raw(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let len = buf.len().min(MAX_RW_LEN);
+		retry_eintr(|| unsafe { check_ret(libc::read(self.as_raw_fd(), buf.as_mut_ptr().cast(), len)) }).map(|n| n as usize)
+	}
+
+	/// Read from the file descriptor into multiple buffers with `readv(2)`.
+	///
+	/// The call is retried if it is interrupted by a signal.
+	fn read_vectored_raw(&self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		// IoSliceMut is guaranteed to be ABI compatible with `struct iovec`.
+		let count = bufs.len().min(max_iov() as usize) as c_int;
+		retry_eintr(|| unsafe { check_ret(libc::readv(self.as_raw_fd(), bufs.as_mut_ptr().cast(), count)) }).map(|n| n as usize)
+	}
+
+	/// Write to the file descriptor with `write(2)`.
+	///
+	/// The call is retried if it is interrupted by a signal.
+	fn write_raw(&self, buf: &[u8]) -> std::io::Result<usize> {
+		let len = buf.len().min(MAX_RW_LEN);
+		retry_eintr(|| unsafe { check_ret(libc::write(self.as_raw_fd(), buf.as_ptr().cast(), len)) }).map(|n| n as usize)
+	}
+
+	/// Write multiple buffers to the file descriptor with `writev(2)`.
+	///
+	/// The call is retried if it is interrupted by a signal.
+	fn write_vectored_raw(&self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		// IoSlice is guaranteed to be ABI compatible with `struct iovec`.
+		let count = bufs.len().min(max_iov() as usize) as c_int;
+		retry_eintr(|| unsafe { check_ret(libc::writev(self.as_raw_fd(), bufs.as_ptr().cast(), count)) }).map(|n| n as usize)
+	}
+}
+
+impl Read for FileDesc {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		self.read_raw(buf)
+	}
+
+	fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		self.read_vectored_raw(bufs)
+	}
+}
+
+impl Read for &'_ FileDesc {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		self.read_raw(buf)
+	}
+
+	fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		self.read_vectored_raw(bufs)
+	}
+}
+
+impl Write for FileDesc {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.write_raw(buf)
+	}
+
+	fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		self.write_vectored_raw(bufs)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+impl Write for &'_ FileDesc {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.write_raw(buf)
+	}
+
+	fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		self.write_vectored_raw(bufs)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
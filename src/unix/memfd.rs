@@ -0,0 +1,230 @@
+use std::ffi::{CString, OsStr};
+use std::os::raw::{c_int, c_uint};
+use std::os::unix::ffi::OsStrExt;
+
+use super::{check_ret, FileDesc};
+
+/// Options for creating a memory file with [`FileDesc::memfd()`].
+///
+/// The `MFD_CLOEXEC` flag is always set and can not be disabled.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MemfdFlags {
+	allow_sealing: bool,
+	hugetlb: bool,
+	noexec_seal: bool,
+}
+
+impl MemfdFlags {
+	/// Create new options with all flags disabled.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Allow seals to be added to the file (`MFD_ALLOW_SEALING`).
+	///
+	/// Without this flag, the file is created with the `F_SEAL_SEAL` seal, so no other seals can be added.
+	pub fn allow_sealing(mut self, allow_sealing: bool) -> Self {
+		self.allow_sealing = allow_sealing;
+		self
+	}
+
+	/// Back the file with huge pages (`MFD_HUGETLB`).
+	///
+	/// This requires huge pages to be available on the system.
+	pub fn hugetlb(mut self, hugetlb: bool) -> Self {
+		self.hugetlb = hugetlb;
+		self
+	}
+
+	/// Make the file non-executable and seal it with `F_SEAL_EXEC` (`MFD_NOEXEC_SEAL`).
+	///
+	/// This implies [`Self::allow_sealing()`].
+	/// It requires Linux 6.3 or later.
+	pub fn noexec_seal(mut self, noexec_seal: bool) -> Self {
+		self.noexec_seal = noexec_seal;
+		self
+	}
+
+	/// Get the raw flags for `memfd_create()`, including `MFD_CLOEXEC`.
+	fn to_raw(self) -> c_uint {
+		let mut flags = libc::MFD_CLOEXEC;
+		if self.allow_sealing {
+			flags |= libc::MFD_ALLOW_SEALING;
+		}
+		if self.hugetlb {
+			flags |= libc::MFD_HUGETLB;
+		}
+		if self.noexec_seal {
+			flags |= libc::MFD_NOEXEC_SEAL;
+		}
+		flags
+	}
+}
+
+/// A set of file seals, used with [`FileDesc::add_seals()`] and [`FileDesc::get_seals()`].
+///
+/// See the `F_ADD_SEALS` section of `fcntl(2)` for a full description of the seals.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Seals {
+	seal: bool,
+	shrink: bool,
+	grow: bool,
+	write: bool,
+	future_write: bool,
+	exec: bool,
+}
+
+impl Seals {
+	/// Create an empty set of seals.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Prevent further seals from being added (`F_SEAL_SEAL`).
+	pub fn seal(mut self, seal: bool) -> Self {
+		self.seal = seal;
+		self
+	}
+
+	/// Prevent the file from being truncated to a smaller size (`F_SEAL_SHRINK`).
+	pub fn shrink(mut self, shrink: bool) -> Self {
+		self.shrink = shrink;
+		self
+	}
+
+	/// Prevent the file from growing, by writing past the end or by truncating it to a larger size (`F_SEAL_GROW`).
+	pub fn grow(mut self, grow: bool) -> Self {
+		self.grow = grow;
+		self
+	}
+
+	/// Prevent the contents of the file from being modified (`F_SEAL_WRITE`).
+	///
+	/// Adding this seal fails with `EBUSY` if the file has writable shared memory mappings.
+	pub fn write(mut self, write: bool) -> Self {
+		self.write = write;
+		self
+	}
+
+	/// Prevent new writes to the file, while allowing existing writable mappings (`F_SEAL_FUTURE_WRITE`).
+	pub fn future_write(mut self, future_write: bool) -> Self {
+		self.future_write = future_write;
+		self
+	}
+
+	/// Prevent the executable permission bits of the file from being changed (`F_SEAL_EXEC`).
+	pub fn exec(mut self, exec: bool) -> Self {
+		self.exec = exec;
+		self
+	}
+
+	/// Check if all seals in `other` are also in `self`.
+	pub fn contains(self, other: Seals) -> bool {
+		self.to_raw() & other.to_raw() == other.to_raw()
+	}
+
+	/// Check if the set is empty.
+	pub fn is_empty(self) -> bool {
+		self.to_raw() == 0
+	}
+
+	/// Convert the seals to the raw value for `F_ADD_SEALS`.
+	fn to_raw(self) -> c_int {
+		let mut seals = 0;
+		for (value, flag) in self.flags() {
+			if value {
+				seals |= flag;
+			}
+		}
+		seals
+	}
+
+	/// Create a set of seals from the raw value returned by `F_GET_SEALS`.
+	///
+	/// Unknown seals are ignored.
+	fn from_raw(raw: c_int) -> Self {
+		Self {
+			seal: raw & libc::F_SEAL_SEAL != 0,
+			shrink: raw & libc::F_SEAL_SHRINK != 0,
+			grow: raw & libc::F_SEAL_GROW != 0,
+			write: raw & libc::F_SEAL_WRITE != 0,
+			future_write: raw & libc::F_SEAL_FUTURE_WRITE != 0,
+			exec: raw & libc::F_SEAL_EXEC != 0,
+		}
+	}
+
+	/// Get the value and raw flag of all seals.
+	fn flags(self) -> [(bool, c_int); 6] {
+		[
+			(self.seal, libc::F_SEAL_SEAL),
+			(self.shrink, libc::F_SEAL_SHRINK),
+			(self.grow, libc::F_SEAL_GROW),
+			(self.write, libc::F_SEAL_WRITE),
+			(self.future_write, libc::F_SEAL_FUTURE_WRITE),
+			(self.exec, libc::F_SEAL_EXEC),
+		]
+	}
+}
+
+impl FileDesc {
+	/// Create an anonymous memory file with `memfd_create()`.
+	///
+	/// The name is only used for debugging: it shows up as the target of the `/proc/self/fd` link.
+	/// It does not have to be unique.
+	///
+	/// The new file descriptor will have the `close-on-exec` flag set atomically.
+	pub fn memfd(name: impl AsRef<OsStr>, flags: MemfdFlags) -> std::io::Result<Self> {
+		let name = CString::new(name.as_ref().as_bytes())
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "name contains an interior nul byte"))?;
+		unsafe {
+			let fd = check_ret(libc::memfd_create(name.as_ptr(), flags.to_raw()))?;
+			Ok(Self::from_raw_fd(fd))
+		}
+	}
+
+	/// Add seals to the file (`F_ADD_SEALS`).
+	///
+	/// Seals can not be removed once they are added.
+	/// Adding seals fails with `EPERM` if the file has the `F_SEAL_SEAL` seal,
+	/// and with `EINVAL` if the file does not support sealing.
+	pub fn add_seals(&self, seals: Seals) -> std::io::Result<()> {
+		unsafe {
+			check_ret(libc::fcntl(self.as_raw_fd(), libc::F_ADD_SEALS, seals.to_raw()))?;
+		}
+		Ok(())
+	}
+
+	/// Get the seals of the file (`F_GET_SEALS`).
+	///
+	/// This fails with `EINVAL` if the file does not support sealing.
+	pub fn get_seals(&self) -> std::io::Result<Seals> {
+		unsafe {
+			let seals = check_ret(libc::fcntl(self.as_raw_fd(), libc::F_GET_SEALS))?;
+			Ok(Seals::from_raw(seals))
+		}
+	}
+
+	/// Verify that the file has all the given seals.
+	///
+	/// This is useful to validate a memory file received from an untrusted process before mapping it.
+	/// For example, checking for [`Seals::shrink()`], [`Seals::grow()`] and [`Seals::write()`]
+	/// guarantees that the contents of the file can not change while it is mapped.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidData`] if a seal is missing,
+	/// or if the file does not support sealing.
+	pub fn require_seals(&self, seals: Seals) -> std::io::Result<()> {
+		let actual = match self.get_seals() {
+			Ok(actual) => actual,
+			Err(e) if e.raw_os_error() == Some(libc::EINVAL) => Seals::new(),
+			Err(e) => return Err(e),
+		};
+		if actual.contains(seals) {
+			Ok(())
+		} else {
+			Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				format!("file is missing required seals: expected {seals:?}, got {actual:?}"),
+			))
+		}
+	}
+}
@@ -0,0 +1,306 @@
+use std::mem::ManuallyDrop;
+use std::os::unix::io::FromRawFd;
+
+use super::{check_ret, FileDesc};
+
+/// The type of file referred to by a file descriptor.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum FileType {
+	/// A regular file.
+	Regular,
+
+	/// A directory.
+	Directory,
+
+	/// A symbolic link.
+	///
+	/// A file descriptor can only refer to a symbolic link if it was opened with `O_PATH | O_NOFOLLOW`.
+	Symlink,
+
+	/// A pipe or named FIFO.
+	Fifo,
+
+	/// A socket.
+	Socket,
+
+	/// A character device.
+	CharDevice,
+
+	/// A block device.
+	BlockDevice,
+
+	/// An event notification object created by `eventfd()`.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	EventFd,
+
+	/// A timer created by `timerfd_create()`.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	TimerFd,
+
+	/// A signal queue created by `signalfd()`.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	SignalFd,
+
+	/// An epoll instance created by `epoll_create()`.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	Epoll,
+
+	/// A process file descriptor created by `pidfd_open()` or `clone()` with `CLONE_PIDFD`.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	PidFd,
+
+	/// An anonymous memory file created by `memfd_create()`.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	MemFd,
+
+	/// A different kind of anonymous inode, not backed by a file system.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	AnonInode,
+
+	/// A type of file not known to this library.
+	Unknown,
+}
+
+impl FileType {
+	/// Get the file type from the `st_mode` field of a `struct stat`.
+	pub(crate) fn from_mode(mode: libc::mode_t) -> Self {
+		match mode & libc::S_IFMT {
+			libc::S_IFREG => Self::Regular,
+			libc::S_IFDIR => Self::Directory,
+			libc::S_IFLNK => Self::Symlink,
+			libc::S_IFIFO => Self::Fifo,
+			libc::S_IFSOCK => Self::Socket,
+			libc::S_IFCHR => Self::CharDevice,
+			libc::S_IFBLK => Self::BlockDevice,
+			_ => Self::Unknown,
+		}
+	}
+
+	/// Get the file type of special files from the target of the `/proc/self/fd` link and the `st_mode` field.
+	///
+	/// A memory file is only recognized if it is a regular file and the link target looks like `/memfd:name (deleted)`,
+	/// so that files in a real directory named `/memfd:...` are not misclassified.
+	///
+	/// Returns `None` if the link target does not identify a special file.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	fn from_proc_link(target: &[u8], mode: libc::mode_t) -> Option<Self> {
+		if let Some(kind) = target.strip_prefix(b"anon_inode:") {
+			match kind {
+				b"[eventfd]" => Some(Self::EventFd),
+				b"[timerfd]" => Some(Self::TimerFd),
+				b"[signalfd]" => Some(Self::SignalFd),
+				b"[eventpoll]" => Some(Self::Epoll),
+				b"[pidfd]" => Some(Self::PidFd),
+				_ => Some(Self::AnonInode),
+			}
+		} else if mode & libc::S_IFMT == libc::S_IFREG && target.starts_with(b"/memfd:") && target.ends_with(b" (deleted)") {
+			Some(Self::MemFd)
+		} else {
+			None
+		}
+	}
+}
+
+/// Unique identifier for an inode on the system, consisting of the device ID and inode number.
+///
+/// Two file descriptors refer to the same file if and only if they have the same [`InodeKey`].
+/// The key can be used to deduplicate file descriptors in a hash map.
+///
+/// Note that inode numbers may be reused by the file system once a file is deleted and no longer open.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct InodeKey {
+	/// The ID of the device containing the file (`st_dev`).
+	pub dev: u64,
+
+	/// The inode number of the file (`st_ino`).
+	pub ino: u64,
+}
+
+impl InodeKey {
+	/// Get the inode key from a `struct stat`.
+	#[allow(clippy::unnecessary_cast)]
+	pub(crate) fn from_stat(stat: &libc::stat) -> Self {
+		Self {
+			dev: stat.st_dev as u64,
+			ino: stat.st_ino as u64,
+		}
+	}
+}
+
+/// Metadata of a file, as returned by [`FileDesc::stat()`] and [`crate::Dir::stat()`].
+#[derive(Copy, Clone)]
+pub struct Stat {
+	inner: libc::stat,
+}
+
+#[allow(clippy::unnecessary_cast)]
+impl Stat {
+	/// Wrap a raw `struct stat`.
+	pub(crate) fn from_raw(inner: libc::stat) -> Self {
+		Self { inner }
+	}
+
+	/// Get the raw `struct stat`.
+	pub fn as_raw(&self) -> &libc::stat {
+		&self.inner
+	}
+
+	/// Get the type of the file.
+	///
+	/// This only looks at the file mode, so anonymous inodes are not identified.
+	pub fn file_type(&self) -> FileType {
+		FileType::from_mode(self.inner.st_mode)
+	}
+
+	/// Get the permission bits of the file, including the set-user-ID, set-group-ID and sticky bits.
+	pub fn permissions(&self) -> u32 {
+		self.inner.st_mode as u32 & 0o7777
+	}
+
+	/// Get the size of the file in bytes.
+	pub fn len(&self) -> u64 {
+		self.inner.st_size as u64
+	}
+
+	/// Check if the size of the file is zero.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Get the [`InodeKey`] of the file.
+	pub fn inode_key(&self) -> InodeKey {
+		InodeKey::from_stat(&self.inner)
+	}
+
+	/// Get the ID of the device containing the file.
+	pub fn dev(&self) -> u64 {
+		self.inner.st_dev as u64
+	}
+
+	/// Get the inode number of the file.
+	pub fn ino(&self) -> u64 {
+		self.inner.st_ino as u64
+	}
+
+	/// Get the number of hard links to the file.
+	pub fn nlink(&self) -> u64 {
+		self.inner.st_nlink as u64
+	}
+
+	/// Get the user ID of the owner of the file.
+	pub fn uid(&self) -> u32 {
+		self.inner.st_uid as u32
+	}
+
+	/// Get the group ID of the owner of the file.
+	pub fn gid(&self) -> u32 {
+		self.inner.st_gid as u32
+	}
+}
+
+impl std::fmt::Debug for Stat {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Stat")
+			.field("file_type", &self.file_type())
+			.field("permissions", &format_args!("{:#o}", self.permissions()))
+			.field("len", &self.len())
+			.field("dev", &self.dev())
+			.field("ino", &self.ino())
+			.field("nlink", &self.nlink())
+			.field("uid", &self.uid())
+			.field("gid", &self.gid())
+			.finish_non_exhaustive()
+	}
+}
+
+impl FileDesc {
+	/// Get the metadata of the file referred to by the file descriptor.
+	///
+	/// The returned [`std::fs::Metadata`] is the same as what [`std::fs::File::metadata()`] would return.
+	/// Use [`Self::stat()`] to get the same [`Stat`] type that is returned by [`crate::Dir::stat()`].
+	pub fn metadata(&self) -> std::io::Result<std::fs::Metadata> {
+		// Safety: The File is never dropped, so it does not close our file descriptor.
+		let file = ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(self.as_raw_fd()) });
+		file.metadata()
+	}
+
+	/// Get the metadata of the file referred to by the file descriptor, using `fstat()`.
+	pub fn stat(&self) -> std::io::Result<Stat> {
+		unsafe {
+			let mut stat: libc::stat = std::mem::zeroed();
+			check_ret(libc::fstat(self.as_raw_fd(), &mut stat))?;
+			Ok(Stat::from_raw(stat))
+		}
+	}
+
+	/// Get the type of the file referred to by the file descriptor.
+	///
+	/// On Linux, anonymous inodes such as those created by `eventfd()` or `timerfd_create()` are identified by
+	/// the target of the `/proc/self/fd` link for the file descriptor.
+	/// If `/proc` is not available, those are reported based on the output of `fstat()` instead.
+	pub fn file_type(&self) -> std::io::Result<FileType> {
+		let mode = self.stat()?.as_raw().st_mode;
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		{
+			use std::os::unix::ffi::OsStrExt;
+			if let Ok(target) = std::fs::read_link(format!("/proc/self/fd/{}", self.as_raw_fd())) {
+				if let Some(file_type) = FileType::from_proc_link(target.as_os_str().as_bytes(), mode) {
+					return Ok(file_type);
+				}
+			}
+		}
+
+		Ok(FileType::from_mode(mode))
+	}
+
+	/// Get the [`InodeKey`] of the file referred to by the file descriptor.
+	pub fn inode_key(&self) -> std::io::Result<InodeKey> {
+		Ok(self.stat()?.inode_key())
+	}
+
+	/// Check if two file descriptors refer to the same file, based on the device ID and inode number.
+	///
+	/// This is also true for file descriptors that were opened separately.
+	/// To check if two file descriptors share the same open file description, use [`Self::same_open_file()`].
+	pub fn same_inode(&self, other: &FileDesc) -> std::io::Result<bool> {
+		Ok(self.inode_key()? == other.inode_key()?)
+	}
+
+	/// Check if two file descriptors refer to the same open file description.
+	///
+	/// This is true for file descriptors that were created by duplicating each other.
+	/// File descriptors that share an open file description also share the file position and file status flags.
+	///
+	/// On Linux, this uses `kcmp()` with `KCMP_FILE`.
+	/// If `kcmp()` is not available (or on other platforms), the library falls back to comparing inodes with [`Self::same_inode()`].
+	/// If the inodes differ, the file descriptors can not share an open file description and `false` is returned.
+	/// Otherwise, the answer can not be determined and an error of kind [`std::io::ErrorKind::Unsupported`] is returned.
+	pub fn same_open_file(&self, other: &FileDesc) -> std::io::Result<bool> {
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		{
+			// Not exported by the libc crate.
+			const KCMP_FILE: libc::c_int = 0;
+			unsafe {
+				let pid = libc::getpid();
+				let ret = libc::syscall(libc::SYS_kcmp, pid, pid, KCMP_FILE, self.as_raw_fd(), other.as_raw_fd());
+				if ret != -1 {
+					return Ok(ret == 0);
+				}
+				let error = std::io::Error::last_os_error();
+				if !matches!(error.raw_os_error(), Some(libc::ENOSYS) | Some(libc::EPERM)) {
+					return Err(error);
+				}
+			}
+		}
+
+		if !self.same_inode(other)? {
+			Ok(false)
+		} else {
+			Err(std::io::Error::new(
+				std::io::ErrorKind::Unsupported,
+				"unable to determine if the file descriptors share an open file description",
+			))
+		}
+	}
+}
@@ -0,0 +1,417 @@
+use std::ops::Range;
+use std::os::raw::{c_int, c_void};
+
+use super::{check_ret, FileDesc};
+
+/// Memory protection of a mapping, used with [`FileDesc::map()`] and [`FileDesc::map_mut()`].
+///
+/// By default, mappings are readable only.
+#[derive(Debug, Copy, Clone)]
+pub struct Protection {
+	read: bool,
+	write: bool,
+	exec: bool,
+}
+
+impl Default for Protection {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Protection {
+	/// Create a new protection that allows reading only.
+	pub fn new() -> Self {
+		Self {
+			read: true,
+			write: false,
+			exec: false,
+		}
+	}
+
+	/// Allow reading from the mapping (`PROT_READ`).
+	///
+	/// Mappings are accessed as byte slices, so [`FileDesc::map()`] and [`FileDesc::map_mut()`]
+	/// fail with an error of kind [`std::io::ErrorKind::InvalidInput`] if reading is not allowed.
+	pub fn read(mut self, read: bool) -> Self {
+		self.read = read;
+		self
+	}
+
+	/// Allow writing to the mapping (`PROT_WRITE`).
+	pub fn write(mut self, write: bool) -> Self {
+		self.write = write;
+		self
+	}
+
+	/// Allow executing code from the mapping (`PROT_EXEC`).
+	pub fn exec(mut self, exec: bool) -> Self {
+		self.exec = exec;
+		self
+	}
+
+	/// Get the raw protection flags for `mmap()`.
+	fn to_raw(self) -> c_int {
+		let mut prot = libc::PROT_NONE;
+		if self.read {
+			prot |= libc::PROT_READ;
+		}
+		if self.write {
+			prot |= libc::PROT_WRITE;
+		}
+		if self.exec {
+			prot |= libc::PROT_EXEC;
+		}
+		prot
+	}
+}
+
+/// Options for mapping a file with [`FileDesc::map()`] and [`FileDesc::map_mut()`].
+///
+/// By default, mappings are shared (`MAP_SHARED`).
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MapFlags {
+	private: bool,
+	populate: bool,
+}
+
+impl MapFlags {
+	/// Create new options for a shared mapping.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Create a private copy-on-write mapping (`MAP_PRIVATE`) instead of a shared mapping (`MAP_SHARED`).
+	///
+	/// Writes to a private mapping are not visible to other processes and are not written back to the file.
+	pub fn private(mut self, private: bool) -> Self {
+		self.private = private;
+		self
+	}
+
+	/// Populate the page tables of the mapping up front (`MAP_POPULATE`).
+	///
+	/// This reads the whole mapped range of the file ahead of time, so accessing the mapping later does not block on I/O.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn populate(mut self, populate: bool) -> Self {
+		self.populate = populate;
+		self
+	}
+
+	/// Get the raw flags for `mmap()`.
+	fn to_raw(self) -> c_int {
+		let mut flags = if self.private { libc::MAP_PRIVATE } else { libc::MAP_SHARED };
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		if self.populate {
+			flags |= libc::MAP_POPULATE;
+		}
+		flags
+	}
+}
+
+/// Advice about the expected access pattern of a mapping, used with `madvise()`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Advice {
+	/// No special treatment (`MADV_NORMAL`).
+	Normal,
+
+	/// Expect page references in random order (`MADV_RANDOM`).
+	Random,
+
+	/// Expect page references in sequential order (`MADV_SEQUENTIAL`).
+	Sequential,
+
+	/// Expect access in the near future (`MADV_WILLNEED`).
+	WillNeed,
+}
+
+impl Advice {
+	/// Get the raw value for `madvise()`.
+	fn to_raw(self) -> c_int {
+		match self {
+			Self::Normal => libc::MADV_NORMAL,
+			Self::Random => libc::MADV_RANDOM,
+			Self::Sequential => libc::MADV_SEQUENTIAL,
+			Self::WillNeed => libc::MADV_WILLNEED,
+		}
+	}
+}
+
+/// A memory mapping, unmapped when dropped.
+///
+/// The mapping starts at a page boundary, but the data may start at an offset into the first page
+/// if the requested range did not start at a page boundary.
+struct Mapping {
+	/// The start of the mapping, aligned to a page boundary.
+	ptr: *mut c_void,
+
+	/// The offset of the data from the start of the mapping.
+	offset: usize,
+
+	/// The length of the data.
+	len: usize,
+}
+
+impl Mapping {
+	/// Map a range of a file.
+	fn new(fd: &FileDesc, range: Range<u64>, prot: Protection, flags: MapFlags) -> std::io::Result<Self> {
+		if range.end <= range.start {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "can not map an empty range"));
+		}
+		let len = usize::try_from(range.end - range.start)
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "range is too large to map"))?;
+		if !prot.read {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "mapping requires read protection"));
+		}
+
+		// Reading is required for all mappings, and writing to a shared mapping also requires write access.
+		let access_mode = fd.access_mode()?;
+		if !access_mode.is_readable() || (prot.write && !flags.private && !access_mode.is_writable()) {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::PermissionDenied,
+				format!("access mode {access_mode:?} does not allow the requested memory protection"),
+			));
+		}
+
+		let offset = (range.start % page_size() as u64) as usize;
+		let start = super::io::to_off_t(range.start - offset as u64)?;
+		let map_len = len
+			.checked_add(offset)
+			.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "range is too large to map"))?;
+		unsafe {
+			let ptr = libc::mmap(std::ptr::null_mut(), map_len, prot.to_raw(), flags.to_raw(), fd.as_raw_fd(), start);
+			if ptr == libc::MAP_FAILED {
+				return Err(std::io::Error::last_os_error());
+			}
+			Ok(Self { ptr, offset, len })
+		}
+	}
+
+	/// Get a pointer to the start of the data.
+	fn data(&self) -> *mut u8 {
+		unsafe { self.ptr.cast::<u8>().add(self.offset) }
+	}
+
+	/// Synchronize the mapping with the file using `msync()`.
+	fn flush(&self, flags: c_int) -> std::io::Result<()> {
+		unsafe {
+			check_ret(libc::msync(self.ptr, self.offset + self.len, flags))?;
+		}
+		Ok(())
+	}
+
+	/// Give advice about the expected access pattern using `madvise()`.
+	fn advise(&self, advice: Advice) -> std::io::Result<()> {
+		unsafe {
+			check_ret(libc::madvise(self.ptr, self.offset + self.len, advice.to_raw()))?;
+		}
+		Ok(())
+	}
+
+	/// Resize the mapping using `mremap()`, allowing the kernel to move it.
+	#[cfg(target_os = "linux")]
+	fn remap(&mut self, new_len: usize) -> std::io::Result<()> {
+		if new_len == 0 {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "can not resize a mapping to zero length"));
+		}
+		let new_map_len = new_len
+			.checked_add(self.offset)
+			.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "length is too large to map"))?;
+		unsafe {
+			let ptr = libc::mremap(self.ptr, self.offset + self.len, new_map_len, libc::MREMAP_MAYMOVE);
+			if ptr == libc::MAP_FAILED {
+				return Err(std::io::Error::last_os_error());
+			}
+			self.ptr = ptr;
+		}
+		self.len = new_len;
+		Ok(())
+	}
+}
+
+impl Drop for Mapping {
+	fn drop(&mut self) {
+		unsafe {
+			libc::munmap(self.ptr, self.offset + self.len);
+		}
+	}
+}
+
+// The mapping behaves like an owned byte buffer.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+/// A read-only memory mapping of a file, created with [`FileDesc::map()`].
+///
+/// The memory is unmapped when the value is dropped.
+/// The mapping borrows the [`FileDesc`] it was created from, so the file descriptor can not be closed while the mapping exists.
+pub struct Mmap<'fd> {
+	inner: Mapping,
+	fd: &'fd FileDesc,
+}
+
+/// A writable memory mapping of a file, created with [`FileDesc::map_mut()`].
+///
+/// The memory is unmapped when the value is dropped.
+/// The mapping borrows the [`FileDesc`] it was created from, so the file descriptor can not be closed while the mapping exists.
+pub struct MmapMut<'fd> {
+	inner: Mapping,
+	fd: &'fd FileDesc,
+}
+
+impl FileDesc {
+	/// Map a range of the file into memory with `mmap()`.
+	///
+	/// The range does not need to start at a page boundary: the library maps the containing pages,
+	/// and the returned mapping starts at the requested offset.
+	///
+	/// Mapping fails with an error of kind [`std::io::ErrorKind::PermissionDenied`]
+	/// if the access mode of the file descriptor does not allow the requested protection.
+	/// All mappings require read access, and shared writable mappings also require write access.
+	///
+	/// The requested protection must allow reading and must not allow writing,
+	/// or this function fails with an error of kind [`std::io::ErrorKind::InvalidInput`].
+	/// Use [`Self::map_mut()`] for writable mappings.
+	///
+	/// # Safety
+	/// The mapped memory must not be modified while the mapping exists,
+	/// neither by this process nor by other processes, for example by writing to or truncating the file.
+	/// Accessing a mapped page beyond the end of the file raises `SIGBUS`.
+	///
+	/// For memory files, you can use [`FileDesc::require_seals()`] to guarantee that the file can not be modified.
+	pub unsafe fn map(&self, range: Range<u64>, prot: Protection, flags: MapFlags) -> std::io::Result<Mmap<'_>> {
+		if prot.write {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				"read-only mapping can not have write protection",
+			));
+		}
+		Ok(Mmap {
+			inner: Mapping::new(self, range, prot, flags)?,
+			fd: self,
+		})
+	}
+
+	/// Map a range of the file into memory with `mmap()`, allowing writes to the mapped memory.
+	///
+	/// The requested protection must allow reading and writing, or this function fails with an error of kind [`std::io::ErrorKind::InvalidInput`].
+	/// See [`Self::map()`] for more details.
+	///
+	/// # Safety
+	/// The mapped memory must not be accessed by anything else while the mapping exists,
+	/// including other mappings of the same file in this process or in other processes.
+	/// Accessing a mapped page beyond the end of the file raises `SIGBUS`.
+	pub unsafe fn map_mut(&self, range: Range<u64>, prot: Protection, flags: MapFlags) -> std::io::Result<MmapMut<'_>> {
+		if !prot.write {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				"mutable mapping requires write protection",
+			));
+		}
+		Ok(MmapMut {
+			inner: Mapping::new(self, range, prot, flags)?,
+			fd: self,
+		})
+	}
+}
+
+impl<'fd> Mmap<'fd> {
+	/// Get the [`FileDesc`] that the mapping was created from.
+	pub fn file_desc(&self) -> &'fd FileDesc {
+		self.fd
+	}
+
+	/// Give advice to the kernel about the expected access pattern of the mapping.
+	pub fn advise(&self, advice: Advice) -> std::io::Result<()> {
+		self.inner.advise(advice)
+	}
+
+	/// Resize the mapping, allowing the kernel to move it to a different address.
+	///
+	/// # Safety
+	/// If the mapping is grown beyond the end of the file, accessing the pages past the end raises `SIGBUS`.
+	#[cfg(target_os = "linux")]
+	pub unsafe fn remap(&mut self, new_len: usize) -> std::io::Result<()> {
+		self.inner.remap(new_len)
+	}
+}
+
+impl<'fd> MmapMut<'fd> {
+	/// Get the [`FileDesc`] that the mapping was created from.
+	pub fn file_desc(&self) -> &'fd FileDesc {
+		self.fd
+	}
+
+	/// Write modified pages back to the file and wait for the writes to complete (`msync()` with `MS_SYNC`).
+	///
+	/// This has no effect for private mappings.
+	pub fn flush(&self) -> std::io::Result<()> {
+		self.inner.flush(libc::MS_SYNC)
+	}
+
+	/// Schedule modified pages to be written back to the file, without waiting (`msync()` with `MS_ASYNC`).
+	pub fn flush_async(&self) -> std::io::Result<()> {
+		self.inner.flush(libc::MS_ASYNC)
+	}
+
+	/// Give advice to the kernel about the expected access pattern of the mapping.
+	pub fn advise(&self, advice: Advice) -> std::io::Result<()> {
+		self.inner.advise(advice)
+	}
+
+	/// Resize the mapping, allowing the kernel to move it to a different address.
+	///
+	/// # Safety
+	/// If the mapping is grown beyond the end of the file, accessing the pages past the end raises `SIGBUS`.
+	#[cfg(target_os = "linux")]
+	pub unsafe fn remap(&mut self, new_len: usize) -> std::io::Result<()> {
+		self.inner.remap(new_len)
+	}
+}
+
+impl std::ops::Deref for Mmap<'_> {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		unsafe { std::slice::from_raw_parts(self.inner.data(), self.inner.len) }
+	}
+}
+
+impl std::ops::Deref for MmapMut<'_> {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		unsafe { std::slice::from_raw_parts(self.inner.data(), self.inner.len) }
+	}
+}
+
+impl std::ops::DerefMut for MmapMut<'_> {
+	fn deref_mut(&mut self) -> &mut [u8] {
+		unsafe { std::slice::from_raw_parts_mut(self.inner.data(), self.inner.len) }
+	}
+}
+
+impl std::fmt::Debug for Mmap<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Mmap")
+			.field("fd", &self.fd.as_raw_fd())
+			.field("ptr", &self.inner.data())
+			.field("len", &self.inner.len)
+			.finish()
+	}
+}
+
+impl std::fmt::Debug for MmapMut<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("MmapMut")
+			.field("fd", &self.fd.as_raw_fd())
+			.field("ptr", &self.inner.data())
+			.field("len", &self.inner.len)
+			.finish()
+	}
+}
+
+/// Get the page size of the system.
+fn page_size() -> usize {
+	unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
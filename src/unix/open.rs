@@ -0,0 +1,233 @@
+use std::ffi::CString;
+use std::os::raw::c_int;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use super::{check_ret, retry_eintr, FileDesc};
+
+/// Options for opening a file with [`FileDesc::open()`] or [`FileDesc::open_at()`].
+///
+/// Unlike [`std::fs::OpenOptions`], this gives access to raw flags like `O_PATH` and `O_NOFOLLOW`.
+/// The `O_CLOEXEC` flag is always set and can not be disabled.
+///
+/// By default, files are opened for reading only.
+#[derive(Debug, Copy, Clone)]
+pub struct OpenFlags {
+	read: bool,
+	write: bool,
+	append: bool,
+	truncate: bool,
+	create: bool,
+	create_new: bool,
+	pub(crate) mode: libc::mode_t,
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	tmpfile: bool,
+	custom_flags: c_int,
+}
+
+impl Default for OpenFlags {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl OpenFlags {
+	/// Create new options to open a file for reading only.
+	pub fn new() -> Self {
+		Self {
+			read: true,
+			write: false,
+			append: false,
+			truncate: false,
+			create: false,
+			create_new: false,
+			mode: 0o666,
+			#[cfg(any(target_os = "linux", target_os = "android"))]
+			tmpfile: false,
+			custom_flags: 0,
+		}
+	}
+
+	/// Set or clear a raw flag.
+	fn flag(mut self, flag: c_int, value: bool) -> Self {
+		self.custom_flags = super::set_bit(self.custom_flags, flag, value);
+		self
+	}
+
+	/// Open the file for reading.
+	pub fn read(mut self, read: bool) -> Self {
+		self.read = read;
+		self
+	}
+
+	/// Open the file for writing.
+	pub fn write(mut self, write: bool) -> Self {
+		self.write = write;
+		self
+	}
+
+	/// Open the file in append mode (`O_APPEND`).
+	///
+	/// This implies write access.
+	pub fn append(mut self, append: bool) -> Self {
+		self.append = append;
+		self
+	}
+
+	/// Truncate the file to zero length if it already exists (`O_TRUNC`).
+	pub fn truncate(mut self, truncate: bool) -> Self {
+		self.truncate = truncate;
+		self
+	}
+
+	/// Create the file if it does not exist yet (`O_CREAT`).
+	pub fn create(mut self, create: bool) -> Self {
+		self.create = create;
+		self
+	}
+
+	/// Create the file, failing if it already exists (`O_CREAT | O_EXCL`).
+	pub fn create_new(mut self, create_new: bool) -> Self {
+		self.create_new = create_new;
+		self
+	}
+
+	/// Set the permissions for newly created files.
+	///
+	/// The permissions are still subject to the umask of the process.
+	/// The default is `0o666`.
+	pub fn mode(mut self, mode: u32) -> Self {
+		self.mode = mode as libc::mode_t;
+		self
+	}
+
+	/// Do not follow a symbolic link in the last path component (`O_NOFOLLOW`).
+	///
+	/// If the last path component is a symbolic link, opening the file fails with `ELOOP`,
+	/// unless `O_PATH` is also set.
+	pub fn nofollow(self, nofollow: bool) -> Self {
+		self.flag(libc::O_NOFOLLOW, nofollow)
+	}
+
+	/// Fail if the path does not refer to a directory (`O_DIRECTORY`).
+	pub fn directory(self, directory: bool) -> Self {
+		self.flag(libc::O_DIRECTORY, directory)
+	}
+
+	/// Do not make a terminal device the controlling terminal of the process (`O_NOCTTY`).
+	pub fn noctty(self, noctty: bool) -> Self {
+		self.flag(libc::O_NOCTTY, noctty)
+	}
+
+	/// Open the file in non-blocking mode (`O_NONBLOCK`).
+	pub fn nonblocking(self, nonblocking: bool) -> Self {
+		self.flag(libc::O_NONBLOCK, nonblocking)
+	}
+
+	/// Make every write wait until data and metadata are written to the storage device (`O_SYNC`).
+	pub fn sync(self, sync: bool) -> Self {
+		self.flag(libc::O_SYNC, sync)
+	}
+
+	/// Make every write wait until data is written to the storage device (`O_DSYNC`).
+	pub fn dsync(self, dsync: bool) -> Self {
+		self.flag(libc::O_DSYNC, dsync)
+	}
+
+	/// Only obtain a file descriptor that identifies a location in the file system (`O_PATH`).
+	///
+	/// The file descriptor can not be used for reading or writing,
+	/// but it can be used as directory for [`FileDesc::open_at()`] and similar functions.
+	/// When set, the read and write options are ignored.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn path(self, path: bool) -> Self {
+		self.flag(libc::O_PATH, path)
+	}
+
+	/// Do not update the last access time of the file when reading (`O_NOATIME`).
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn noatime(self, noatime: bool) -> Self {
+		self.flag(libc::O_NOATIME, noatime)
+	}
+
+	/// Bypass the page cache when reading and writing (`O_DIRECT`).
+	#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+	pub fn direct(self, direct: bool) -> Self {
+		self.flag(libc::O_DIRECT, direct)
+	}
+
+	/// Create an unnamed temporary file in the directory given by the path (`O_TMPFILE`).
+	///
+	/// This requires write access.
+	/// Not all file systems support unnamed temporary files.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn tmpfile(mut self, tmpfile: bool) -> Self {
+		// O_TMPFILE includes O_DIRECTORY, so it can not be set or cleared as a single bit.
+		self.tmpfile = tmpfile;
+		self
+	}
+
+	/// Get the raw flags for `open()`, including `O_CLOEXEC`.
+	pub(crate) fn to_raw(self) -> c_int {
+		let access = match (self.read, self.write || self.append) {
+			(_, false) => libc::O_RDONLY,
+			(false, true) => libc::O_WRONLY,
+			(true, true) => libc::O_RDWR,
+		};
+		let mut flags = access | self.custom_flags | libc::O_CLOEXEC;
+		if self.append {
+			flags |= libc::O_APPEND;
+		}
+		if self.truncate {
+			flags |= libc::O_TRUNC;
+		}
+		if self.create_new {
+			flags |= libc::O_CREAT | libc::O_EXCL;
+		} else if self.create {
+			flags |= libc::O_CREAT;
+		}
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		if self.tmpfile {
+			flags |= libc::O_TMPFILE;
+		}
+		flags
+	}
+}
+
+impl FileDesc {
+	/// Open a file.
+	///
+	/// The new file descriptor will have the `close-on-exec` flag set atomically.
+	/// Relative paths are resolved relative to the current working directory.
+	///
+	/// The call is retried if it is interrupted by a signal.
+	pub fn open(path: impl AsRef<Path>, flags: OpenFlags) -> std::io::Result<Self> {
+		open_at(libc::AT_FDCWD, path.as_ref(), flags)
+	}
+
+	/// Open a file relative to a directory file descriptor, using `openat()`.
+	///
+	/// Relative paths are resolved relative to the directory referred to by `self`.
+	/// Absolute paths ignore `self`.
+	///
+	/// The new file descriptor will have the `close-on-exec` flag set atomically.
+	/// The call is retried if it is interrupted by a signal.
+	pub fn open_at(&self, path: impl AsRef<Path>, flags: OpenFlags) -> std::io::Result<Self> {
+		open_at(self.as_raw_fd(), path.as_ref(), flags)
+	}
+}
+
+/// Open a file relative to a raw directory file descriptor (or `AT_FDCWD`).
+fn open_at(dir: c_int, path: &Path, flags: OpenFlags) -> std::io::Result<FileDesc> {
+	let path = path_to_cstring(path)?;
+	unsafe {
+		let fd = retry_eintr(|| check_ret(libc::openat(dir, path.as_ptr(), flags.to_raw(), flags.mode as libc::c_uint)))?;
+		Ok(FileDesc::from_raw_fd(fd))
+	}
+}
+
+/// Convert a path to a C string, failing if it contains a nul byte.
+pub(crate) fn path_to_cstring(path: &Path) -> std::io::Result<CString> {
+	CString::new(path.as_os_str().as_bytes())
+		.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "path contains an interior nul byte"))
+}
@@ -0,0 +1,212 @@
+use std::io::{IoSlice, IoSliceMut, Read, Write};
+use std::os::raw::c_int;
+use std::os::unix::io::RawFd;
+
+use super::{check_ret, FileDesc};
+
+/// Options for creating a pipe with [`FileDesc::pipe()`].
+///
+/// The `close-on-exec` flag is always set on both ends of the pipe.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PipeFlags {
+	nonblocking: bool,
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	direct: bool,
+}
+
+impl PipeFlags {
+	/// Create new pipe flags with all options disabled.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Put both ends of the pipe in non-blocking mode (`O_NONBLOCK`).
+	pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+		self.nonblocking = nonblocking;
+		self
+	}
+
+	/// Create the pipe in packet mode (`O_DIRECT`).
+	///
+	/// In packet mode, each write is a separate packet, and each read returns at most one packet.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn direct(mut self, direct: bool) -> Self {
+		self.direct = direct;
+		self
+	}
+}
+
+/// The read end of a pipe.
+#[derive(Debug)]
+pub struct PipeReader {
+	fd: FileDesc,
+}
+
+/// The write end of a pipe.
+#[derive(Debug)]
+pub struct PipeWriter {
+	fd: FileDesc,
+}
+
+impl FileDesc {
+	/// Create a new pipe.
+	///
+	/// Returns the read end and the write end of the pipe, in that order.
+	///
+	/// Both ends will have the `close-on-exec` flag set.
+	/// If the platform supports it, the pipe is created with `pipe2()` and the flags are set atomically.
+	/// Otherwise, the library falls back to `pipe()` and sets the flags non-atomically.
+	pub fn pipe(flags: PipeFlags) -> std::io::Result<(PipeReader, PipeWriter)> {
+		let mut fds = [-1; 2];
+
+		#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+		unsafe {
+			let mut raw_flags = libc::O_CLOEXEC;
+			if flags.nonblocking {
+				raw_flags |= libc::O_NONBLOCK;
+			}
+			#[cfg(any(target_os = "linux", target_os = "android"))]
+			if flags.direct {
+				raw_flags |= libc::O_DIRECT;
+			}
+			check_ret(libc::pipe2(fds.as_mut_ptr(), raw_flags))?;
+			Ok((PipeReader::from_raw_fd(fds[0]), PipeWriter::from_raw_fd(fds[1])))
+		}
+
+		#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+		unsafe {
+			check_ret(libc::pipe(fds.as_mut_ptr()))?;
+			let reader = PipeReader::from_raw_fd(fds[0]);
+			let writer = PipeWriter::from_raw_fd(fds[1]);
+			for fd in [&reader.fd, &writer.fd] {
+				fd.set_close_on_exec(true)?;
+				if flags.nonblocking {
+					fd.set_nonblocking(true)?;
+				}
+			}
+			Ok((reader, writer))
+		}
+	}
+
+	/// Get the capacity of a pipe in bytes (`F_GETPIPE_SZ`).
+	///
+	/// The file descriptor can be either end of the pipe.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn pipe_capacity(&self) -> std::io::Result<usize> {
+		unsafe {
+			let size = check_ret(libc::fcntl(self.as_raw_fd(), libc::F_GETPIPE_SZ))?;
+			Ok(size as usize)
+		}
+	}
+
+	/// Change the capacity of a pipe (`F_SETPIPE_SZ`).
+	///
+	/// The kernel may round the capacity up.
+	/// Returns the actual new capacity of the pipe.
+	///
+	/// Unprivileged processes can not increase the capacity beyond `/proc/sys/fs/pipe-max-size`.
+	/// The file descriptor can be either end of the pipe.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn set_pipe_capacity(&self, capacity: usize) -> std::io::Result<usize> {
+		let capacity = c_int::try_from(capacity).map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "pipe capacity too large"))?;
+		unsafe {
+			let size = check_ret(libc::fcntl(self.as_raw_fd(), libc::F_SETPIPE_SZ, capacity))?;
+			Ok(size as usize)
+		}
+	}
+}
+
+macro_rules! impl_pipe_end {
+	($type:ident) => {
+		impl $type {
+			/// Wrap a raw file descriptor.
+			///
+			/// # Safety
+			/// The file descriptor must be a valid file descriptor that is not owned by anything else.
+			unsafe fn from_raw_fd(fd: RawFd) -> Self {
+				Self {
+					fd: unsafe { FileDesc::from_raw_fd(fd) },
+				}
+			}
+
+			/// Get a reference to the wrapped [`FileDesc`].
+			pub fn as_file_desc(&self) -> &FileDesc {
+				&self.fd
+			}
+
+			/// Release the wrapped [`FileDesc`].
+			pub fn into_file_desc(self) -> FileDesc {
+				self.fd
+			}
+
+			/// Get the capacity of the pipe in bytes.
+			///
+			/// See [`FileDesc::pipe_capacity()`] for more details.
+			#[cfg(any(target_os = "linux", target_os = "android"))]
+			pub fn capacity(&self) -> std::io::Result<usize> {
+				self.fd.pipe_capacity()
+			}
+
+			/// Change the capacity of the pipe.
+			///
+			/// See [`FileDesc::set_pipe_capacity()`] for more details.
+			#[cfg(any(target_os = "linux", target_os = "android"))]
+			pub fn set_capacity(&self, capacity: usize) -> std::io::Result<usize> {
+				self.fd.set_pipe_capacity(capacity)
+			}
+		}
+
+		impl_file_desc_wrapper!($type);
+	};
+}
+
+impl_pipe_end!(PipeReader);
+impl_pipe_end!(PipeWriter);
+
+impl Read for PipeReader {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		(&self.fd).read(buf)
+	}
+
+	fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		(&self.fd).read_vectored(bufs)
+	}
+}
+
+impl Read for &'_ PipeReader {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		(&self.fd).read(buf)
+	}
+
+	fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		(&self.fd).read_vectored(bufs)
+	}
+}
+
+impl Write for PipeWriter {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		(&self.fd).write(buf)
+	}
+
+	fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		(&self.fd).write_vectored(bufs)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+impl Write for &'_ PipeWriter {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		(&self.fd).write(buf)
+	}
+
+	fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		(&self.fd).write_vectored(bufs)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
@@ -0,0 +1,356 @@
+use std::ffi::{CStr, OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+
+use super::{check_ret, Dir, FileDesc, FileType, OpenFlags};
+
+/// Iterator over the entries of a directory file descriptor.
+///
+/// On Linux and Android, the entries are read with `getdents64()` directly from the file descriptor.
+/// On other platforms, the library falls back to `fdopendir()` and `readdir()`.
+///
+/// The `.` and `..` entries are skipped.
+///
+/// The directory offset is shared by all duplicates of the file descriptor,
+/// so you should not use other duplicates to read the directory at the same time.
+/// Use [`Dir::read_dir()`] to iterate over a directory with a new, independent offset.
+pub struct ReadDir {
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	fd: FileDesc,
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	buffer: Vec<u8>,
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	position: usize,
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	len: usize,
+
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	dir: std::ptr::NonNull<libc::DIR>,
+
+	done: bool,
+}
+
+/// An entry in a directory.
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+	name: OsString,
+	ino: u64,
+	file_type: Option<FileType>,
+}
+
+impl DirEntry {
+	/// Get the file name of the entry.
+	pub fn file_name(&self) -> &OsStr {
+		&self.name
+	}
+
+	/// Get the inode number of the entry.
+	pub fn ino(&self) -> u64 {
+		self.ino
+	}
+
+	/// Get the file type of the entry, if the file system reported it.
+	///
+	/// This does not require an additional system call,
+	/// but some file systems do not report the file type in directory entries.
+	/// In that case, `None` is returned and you have to use [`Dir::stat()`] to get the file type.
+	///
+	/// On platforms where directory entries have no `d_type` field, like Solaris,
+	/// the file type is determined with `fstatat()` while reading the directory.
+	pub fn file_type(&self) -> Option<FileType> {
+		self.file_type
+	}
+}
+
+/// Convert the `d_type` field of a directory entry to a [`FileType`].
+#[cfg(any(
+	target_os = "linux",
+	target_os = "android",
+	target_vendor = "apple",
+	target_os = "freebsd",
+	target_os = "dragonfly",
+	target_os = "openbsd",
+	target_os = "netbsd",
+))]
+fn file_type_from_dirent(d_type: u8) -> Option<FileType> {
+	match d_type {
+		libc::DT_REG => Some(FileType::Regular),
+		libc::DT_DIR => Some(FileType::Directory),
+		libc::DT_LNK => Some(FileType::Symlink),
+		libc::DT_FIFO => Some(FileType::Fifo),
+		libc::DT_SOCK => Some(FileType::Socket),
+		libc::DT_CHR => Some(FileType::CharDevice),
+		libc::DT_BLK => Some(FileType::BlockDevice),
+		_ => None,
+	}
+}
+
+/// Create a directory entry from the raw fields, or `None` for the `.` and `..` entries.
+///
+/// The file type is only determined for other entries, since it may require a system call.
+fn make_entry(name: &CStr, ino: u64, file_type: impl FnOnce() -> Option<FileType>) -> Option<DirEntry> {
+	let name = name.to_bytes();
+	if name == b"." || name == b".." {
+		return None;
+	}
+	Some(DirEntry {
+		name: OsStr::from_bytes(name).to_owned(),
+		ino,
+		file_type: file_type(),
+	})
+}
+
+/// Create an error for a malformed directory entry returned by the kernel.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn invalid_entry() -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed directory entry")
+}
+
+impl Dir {
+	/// Iterate over the entries of the directory.
+	///
+	/// The directory is opened again with `openat()`, so the iterator has its own directory offset,
+	/// independent of the directory offset of `self`.
+	pub fn read_dir(&self) -> std::io::Result<ReadDir> {
+		let fd = self.as_file_desc().open_at(".", OpenFlags::new().directory(true))?;
+		ReadDir::new(fd)
+	}
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl ReadDir {
+	/// Create an iterator over the entries of a directory file descriptor.
+	///
+	/// The directory offset is rewound to the start first.
+	/// The file descriptor can be a duplicate of another directory file descriptor,
+	/// but note that duplicates share the directory offset.
+	pub fn new(fd: FileDesc) -> std::io::Result<Self> {
+		let mut read_dir = Self {
+			fd,
+			buffer: vec![0; 32 * 1024],
+			position: 0,
+			len: 0,
+			done: false,
+		};
+		read_dir.rewind()?;
+		Ok(read_dir)
+	}
+
+	/// Rewind the iterator to the start of the directory.
+	pub fn rewind(&mut self) -> std::io::Result<()> {
+		unsafe {
+			if libc::lseek(self.fd.as_raw_fd(), 0, libc::SEEK_SET) == -1 {
+				return Err(std::io::Error::last_os_error());
+			}
+		}
+		self.position = 0;
+		self.len = 0;
+		self.done = false;
+		Ok(())
+	}
+
+	/// Release the wrapped [`FileDesc`].
+	pub fn into_file_desc(self) -> FileDesc {
+		self.fd
+	}
+
+	/// Read the next batch of entries into the buffer.
+	///
+	/// Returns false if the end of the directory was reached.
+	fn fill_buffer(&mut self) -> std::io::Result<bool> {
+		let len = unsafe {
+			let ret = libc::syscall(libc::SYS_getdents64, self.fd.as_raw_fd(), self.buffer.as_mut_ptr(), self.buffer.len());
+			check_ret(ret as isize)? as usize
+		};
+		self.position = 0;
+		self.len = len;
+		Ok(len > 0)
+	}
+
+	/// Parse the next raw entry from the buffer, including `.` and `..`.
+	fn next_raw(&mut self) -> Option<std::io::Result<Option<DirEntry>>> {
+		if self.done {
+			return None;
+		}
+		if self.position >= self.len {
+			match self.fill_buffer() {
+				Ok(true) => (),
+				Ok(false) => {
+					self.done = true;
+					return None;
+				},
+				Err(e) => {
+					self.done = true;
+					return Some(Err(e));
+				},
+			}
+		}
+
+		// Layout of `struct linux_dirent64`: u64 d_ino, i64 d_off, u16 d_reclen, u8 d_type, char d_name[].
+		let record = &self.buffer[self.position..self.len];
+		let Some(reclen) = record.get(16..18).map(|reclen| u16::from_ne_bytes(reclen.try_into().unwrap()) as usize) else {
+			self.done = true;
+			return Some(Err(invalid_entry()));
+		};
+		let Some(name) = record.get(19..reclen).and_then(|name| CStr::from_bytes_until_nul(name).ok()) else {
+			self.done = true;
+			return Some(Err(invalid_entry()));
+		};
+		let ino = u64::from_ne_bytes(record[0..8].try_into().unwrap());
+		let d_type = record[18];
+		let entry = make_entry(name, ino, || file_type_from_dirent(d_type));
+		self.position += reclen;
+		Some(Ok(entry))
+	}
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+impl ReadDir {
+	/// Create an iterator over the entries of a directory file descriptor.
+	///
+	/// The directory offset is rewound to the start first.
+	/// The file descriptor can be a duplicate of another directory file descriptor,
+	/// but note that duplicates share the directory offset.
+	pub fn new(fd: FileDesc) -> std::io::Result<Self> {
+		unsafe {
+			let dir = libc::fdopendir(fd.as_raw_fd());
+			let dir = std::ptr::NonNull::new(dir).ok_or_else(std::io::Error::last_os_error)?;
+			// The DIR now owns the file descriptor.
+			let _ = fd.into_raw_fd();
+			libc::rewinddir(dir.as_ptr());
+			Ok(Self { dir, done: false })
+		}
+	}
+
+	/// Rewind the iterator to the start of the directory.
+	pub fn rewind(&mut self) -> std::io::Result<()> {
+		unsafe {
+			libc::rewinddir(self.dir.as_ptr());
+		}
+		self.done = false;
+		Ok(())
+	}
+
+	/// Parse the next raw entry, including `.` and `..`.
+	fn next_raw(&mut self) -> Option<std::io::Result<Option<DirEntry>>> {
+		if self.done {
+			return None;
+		}
+		unsafe {
+			// readdir() only sets errno on errors, so it must be cleared first to detect them.
+			// If errno can not be cleared, an error is detected if readdir() changed errno.
+			let errno_before = if clear_errno() { 0 } else { errno() };
+			let entry = libc::readdir(self.dir.as_ptr());
+			if entry.is_null() {
+				self.done = true;
+				return match errno() {
+					errno if errno == errno_before => None,
+					errno => Some(Err(std::io::Error::from_raw_os_error(errno))),
+				};
+			}
+			let entry = &*entry;
+			let name = CStr::from_ptr(entry.d_name.as_ptr());
+			#[allow(clippy::unnecessary_cast)]
+			let ino = entry.d_ino as u64;
+			Some(Ok(make_entry(name, ino, || self.entry_file_type(entry, name))))
+		}
+	}
+
+	/// Get the file type of an entry from its `d_type` field.
+	#[cfg(any(
+		target_vendor = "apple",
+		target_os = "freebsd",
+		target_os = "dragonfly",
+		target_os = "openbsd",
+		target_os = "netbsd",
+	))]
+	fn entry_file_type(&self, entry: &libc::dirent, _name: &CStr) -> Option<FileType> {
+		file_type_from_dirent(entry.d_type)
+	}
+
+	/// Get the file type of an entry with `fstatat()`, since the platform has no `d_type` field.
+	#[cfg(not(any(
+		target_vendor = "apple",
+		target_os = "freebsd",
+		target_os = "dragonfly",
+		target_os = "openbsd",
+		target_os = "netbsd",
+	)))]
+	fn entry_file_type(&self, _entry: &libc::dirent, name: &CStr) -> Option<FileType> {
+		unsafe {
+			let mut stat: libc::stat = std::mem::zeroed();
+			let ret = libc::fstatat(libc::dirfd(self.dir.as_ptr()), name.as_ptr(), &mut stat, libc::AT_SYMLINK_NOFOLLOW);
+			check_ret(ret).ok().map(|_| FileType::from_mode(stat.st_mode))
+		}
+	}
+}
+
+/// Get the value of `errno` for the current thread.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn errno() -> libc::c_int {
+	std::io::Error::last_os_error().raw_os_error().unwrap_or(0)
+}
+
+/// Set `errno` to zero for the current thread.
+///
+/// Returns false if the platform has no known way to access `errno`.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn clear_errno() -> bool {
+	match errno_location() {
+		Some(errno) => {
+			unsafe { *errno = 0 };
+			true
+		},
+		None => false,
+	}
+}
+
+/// Get a pointer to `errno` for the current thread, if the platform has a known accessor.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+#[allow(unreachable_code)]
+fn errno_location() -> Option<*mut libc::c_int> {
+	#[cfg(any(target_vendor = "apple", target_os = "freebsd", target_os = "dragonfly"))]
+	return Some(unsafe { libc::__error() });
+	#[cfg(any(target_os = "openbsd", target_os = "netbsd"))]
+	return Some(unsafe { libc::__errno() });
+	#[cfg(any(target_os = "solaris", target_os = "illumos"))]
+	return Some(unsafe { libc::___errno() });
+	#[cfg(any(target_os = "emscripten", target_os = "hurd", target_os = "redox"))]
+	return Some(unsafe { libc::__errno_location() });
+	None
+}
+
+// SAFETY: The `DIR` stream is owned exclusively by the iterator, and it is only accessed through `&mut self`,
+// so it can be moved to and referenced from other threads.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+unsafe impl Send for ReadDir {}
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+unsafe impl Sync for ReadDir {}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+impl Drop for ReadDir {
+	fn drop(&mut self) {
+		unsafe {
+			libc::closedir(self.dir.as_ptr());
+		}
+	}
+}
+
+impl Iterator for ReadDir {
+	type Item = std::io::Result<DirEntry>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			match self.next_raw()? {
+				Ok(Some(entry)) => return Some(Ok(entry)),
+				Ok(None) => continue,
+				Err(e) => return Some(Err(e)),
+			}
+		}
+	}
+}
+
+impl std::fmt::Debug for ReadDir {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ReadDir").finish_non_exhaustive()
+	}
+}
@@ -0,0 +1,275 @@
+use std::collections::VecDeque;
+use std::ffi::{CStr, OsString};
+use std::os::raw::c_int;
+use std::path::{Component, Path};
+
+use super::open::path_to_cstring;
+use super::{check_ret, retry_eintr, Dir, FileDesc, OpenFlags};
+
+/// The maximum number of symbolic links to follow while resolving a path, like the Linux kernel.
+const MAX_SYMLINKS: usize = 40;
+
+/// Restrictions on path resolution for [`Dir::open_resolve()`].
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ResolveFlags {
+	beneath: bool,
+	in_root: bool,
+	no_symlinks: bool,
+	no_magiclinks: bool,
+	no_xdev: bool,
+}
+
+impl ResolveFlags {
+	/// Create new resolve flags with all restrictions disabled.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Fail if the path resolves to a location outside of the directory (`RESOLVE_BENEATH`).
+	///
+	/// Absolute paths, absolute symbolic links and `..` components that would escape the directory are rejected with `EXDEV`.
+	pub fn beneath(mut self, beneath: bool) -> Self {
+		self.beneath = beneath;
+		self
+	}
+
+	/// Resolve the path as if the directory is the root of the file system (`RESOLVE_IN_ROOT`).
+	///
+	/// Absolute paths and absolute symbolic links are resolved relative to the directory,
+	/// and `..` components in the directory itself stay in the directory.
+	pub fn in_root(mut self, in_root: bool) -> Self {
+		self.in_root = in_root;
+		self
+	}
+
+	/// Fail with `ELOOP` if any component of the path is a symbolic link (`RESOLVE_NO_SYMLINKS`).
+	pub fn no_symlinks(mut self, no_symlinks: bool) -> Self {
+		self.no_symlinks = no_symlinks;
+		self
+	}
+
+	/// Fail with `ELOOP` if any component of the path is a "magic link" like those in `/proc/self/fd` (`RESOLVE_NO_MAGICLINKS`).
+	pub fn no_magiclinks(mut self, no_magiclinks: bool) -> Self {
+		self.no_magiclinks = no_magiclinks;
+		self
+	}
+
+	/// Fail with `EXDEV` if the path crosses a mount point (`RESOLVE_NO_XDEV`).
+	pub fn no_xdev(mut self, no_xdev: bool) -> Self {
+		self.no_xdev = no_xdev;
+		self
+	}
+}
+
+impl Dir {
+	/// Open a file relative to the directory with restrictions on path resolution.
+	///
+	/// On Linux, this uses `openat2()` with the `RESOLVE_*` flags.
+	/// If `openat2()` is not available (or on other platforms),
+	/// the path is resolved in userspace one component at a time using `O_NOFOLLOW`,
+	/// which gives the same containment guarantees.
+	/// The userspace fallback never follows magic links.
+	/// A file system change that races with the userspace resolution results in an error, never in an escape.
+	///
+	/// Unlike the other functions of [`Dir`], this allows absolute paths if `RESOLVE_IN_ROOT` is used.
+	///
+	/// The new file descriptor will have the `close-on-exec` flag set atomically.
+	pub fn open_resolve(&self, path: impl AsRef<Path>, flags: OpenFlags, resolve: ResolveFlags) -> std::io::Result<FileDesc> {
+		let path = path.as_ref();
+
+		#[cfg(target_os = "linux")]
+		match openat2(self.as_file_desc(), path, flags, resolve) {
+			Err(e) if e.raw_os_error() == Some(libc::ENOSYS) => (),
+			result => return result,
+		}
+
+		resolve_in_userspace(self.as_file_desc(), path, flags, resolve)
+	}
+}
+
+/// Open a file with `openat2()`.
+#[cfg(target_os = "linux")]
+fn openat2(dir: &FileDesc, path: &Path, flags: OpenFlags, resolve: ResolveFlags) -> std::io::Result<FileDesc> {
+	let path = path_to_cstring(path)?;
+	let mut how: libc::open_how = unsafe { std::mem::zeroed() };
+	how.flags = flags.to_raw() as u32 as u64;
+	// O_TMPFILE includes O_DIRECTORY, so check that all of its bits are set.
+	// The kernel rejects a non-zero mode if no file is created.
+	let raw = flags.to_raw();
+	if raw & libc::O_CREAT != 0 || raw & libc::O_TMPFILE == libc::O_TMPFILE {
+		how.mode = flags.mode as u64;
+	}
+	if resolve.beneath {
+		how.resolve |= libc::RESOLVE_BENEATH;
+	}
+	if resolve.in_root {
+		how.resolve |= libc::RESOLVE_IN_ROOT;
+	}
+	if resolve.no_symlinks {
+		how.resolve |= libc::RESOLVE_NO_SYMLINKS;
+	}
+	if resolve.no_magiclinks {
+		how.resolve |= libc::RESOLVE_NO_MAGICLINKS;
+	}
+	if resolve.no_xdev {
+		how.resolve |= libc::RESOLVE_NO_XDEV;
+	}
+
+	unsafe {
+		let fd = retry_eintr(|| {
+			let ret = libc::syscall(libc::SYS_openat2, dir.as_raw_fd(), path.as_ptr(), &how, std::mem::size_of::<libc::open_how>());
+			check_ret(ret as c_int)
+		})?;
+		Ok(FileDesc::from_raw_fd(fd))
+	}
+}
+
+/// Resolve a path one component at a time, following symbolic links in userspace.
+///
+/// Every component is opened with `O_NOFOLLOW`, so the kernel never follows a symbolic link on our behalf.
+/// Instead, symbolic links are read with `readlinkat()` and their targets are resolved with the same restrictions.
+/// The directories that were walked through are kept open, so `..` never needs to be resolved by the kernel.
+pub(crate) fn resolve_in_userspace(root: &FileDesc, path: &Path, flags: OpenFlags, resolve: ResolveFlags) -> std::io::Result<FileDesc> {
+	let root_dev = if resolve.no_xdev { Some(root.stat()?.dev()) } else { None };
+
+	// The directories between the root and the current directory.
+	// An empty stack means the current directory is the root.
+	let mut stack: Vec<FileDesc> = Vec::new();
+	let mut components = VecDeque::new();
+	push_components(&mut components, &mut stack, path, resolve)?;
+
+	let mut symlinks_followed = 0;
+	let user_nofollow = flags.to_raw() & libc::O_NOFOLLOW != 0;
+
+	while let Some(component) = components.pop_front() {
+		let current = stack.last().unwrap_or(root);
+		let is_last = components.is_empty();
+
+		if component == ".." {
+			if resolve.beneath || resolve.in_root {
+				// Go back to the directory we came from, and stay in the root if `in_root` is set.
+				if stack.pop().is_none() && !resolve.in_root {
+					return Err(escape_error());
+				}
+			} else {
+				// Without containment, `..` can leave the root, so open the real parent.
+				let parent = open_raw(current, c"..", DIR_FLAGS, 0)?;
+				check_dev(&parent, root_dev)?;
+				stack.push(parent);
+			}
+			if is_last {
+				return reopen(stack.last().unwrap_or(root), flags);
+			}
+			continue;
+		}
+
+		let name = path_to_cstring(Path::new(&component))?;
+
+		// Resolve symbolic links, unless the caller asked not to follow a symbolic link in the last component.
+		if !(is_last && user_nofollow) {
+			match read_link(current, &name) {
+				Ok(target) => {
+					if resolve.no_symlinks {
+						return Err(std::io::Error::from_raw_os_error(libc::ELOOP));
+					}
+					symlinks_followed += 1;
+					if symlinks_followed > MAX_SYMLINKS {
+						return Err(std::io::Error::from_raw_os_error(libc::ELOOP));
+					}
+					let mut rest = std::mem::take(&mut components);
+					push_components(&mut components, &mut stack, Path::new(&target), resolve)?;
+					components.append(&mut rest);
+					if components.is_empty() {
+						// The link pointed to the root or its ancestors.
+						return reopen(stack.last().unwrap_or(root), flags);
+					}
+					continue;
+				},
+				// Not a symbolic link.
+				Err(e) if e.raw_os_error() == Some(libc::EINVAL) => (),
+				// Let openat() report missing files, or create them.
+				Err(e) if e.raw_os_error() == Some(libc::ENOENT) && is_last => (),
+				Err(e) => return Err(e),
+			}
+		}
+
+		let next = if is_last {
+			open_raw(current, &name, flags.to_raw() | libc::O_NOFOLLOW, flags.mode)?
+		} else {
+			open_raw(current, &name, DIR_FLAGS | libc::O_NOFOLLOW, 0)?
+		};
+
+		check_dev(&next, root_dev)?;
+
+		if is_last {
+			return Ok(next);
+		}
+		stack.push(next);
+	}
+
+	// The path was empty or resolved to the root directory.
+	reopen(stack.last().unwrap_or(root), flags)
+}
+
+/// Flags used to open intermediate directories.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const DIR_FLAGS: c_int = libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const DIR_FLAGS: c_int = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+
+/// Add the components of a path to the front of the queue.
+///
+/// An absolute path resets the stack to the root if `in_root` is set, and is an error if `beneath` is set.
+/// Otherwise, resolution continues from the real root of the file system.
+fn push_components(components: &mut VecDeque<OsString>, stack: &mut Vec<FileDesc>, path: &Path, resolve: ResolveFlags) -> std::io::Result<()> {
+	for component in path.components() {
+		match component {
+			Component::RootDir => {
+				if resolve.in_root {
+					stack.clear();
+				} else if resolve.beneath {
+					return Err(escape_error());
+				} else {
+					stack.clear();
+					stack.push(FileDesc::open("/", OpenFlags::new().directory(true))?);
+				}
+			},
+			Component::CurDir | Component::Prefix(_) => (),
+			Component::ParentDir => components.push_back(OsString::from("..")),
+			Component::Normal(name) => components.push_back(name.to_owned()),
+		}
+	}
+	Ok(())
+}
+
+/// Open a file relative to a directory with raw flags.
+fn open_raw(dir: &FileDesc, name: &CStr, flags: c_int, mode: libc::mode_t) -> std::io::Result<FileDesc> {
+	unsafe {
+		let fd = retry_eintr(|| check_ret(libc::openat(dir.as_raw_fd(), name.as_ptr(), flags, mode as libc::c_uint)))?;
+		Ok(FileDesc::from_raw_fd(fd))
+	}
+}
+
+/// Open a directory that was already resolved again, with the final open flags.
+fn reopen(dir: &FileDesc, flags: OpenFlags) -> std::io::Result<FileDesc> {
+	open_raw(dir, c".", flags.to_raw(), flags.mode)
+}
+
+/// Read the target of a symbolic link, returning `EINVAL` if the file is not a symbolic link.
+fn read_link(dir: &FileDesc, name: &CStr) -> std::io::Result<OsString> {
+	let path = super::dir::read_link_at(dir.as_raw_fd(), name)?;
+	Ok(path.into_os_string())
+}
+
+/// The error returned when a path tries to escape the directory.
+fn escape_error() -> std::io::Error {
+	std::io::Error::from_raw_os_error(libc::EXDEV)
+}
+
+/// Check that a file is on the same device as the root, if requested.
+fn check_dev(fd: &FileDesc, root_dev: Option<u64>) -> std::io::Result<()> {
+	match root_dev {
+		Some(root_dev) if fd.stat()?.dev() != root_dev => Err(std::io::Error::from_raw_os_error(libc::EXDEV)),
+		_ => Ok(()),
+	}
+}
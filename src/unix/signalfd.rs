@@ -0,0 +1,243 @@
+use std::os::raw::c_int;
+
+use super::{check_ret, FileDesc};
+
+/// A set of signals, used with [`SignalFd`].
+#[derive(Copy, Clone)]
+pub struct SignalSet {
+	inner: libc::sigset_t,
+}
+
+impl Default for SignalSet {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl SignalSet {
+	/// Create an empty signal set.
+	pub fn new() -> Self {
+		unsafe {
+			let mut inner = std::mem::zeroed();
+			libc::sigemptyset(&mut inner);
+			Self { inner }
+		}
+	}
+
+	/// Create a signal set from a list of signal numbers, like [`libc::SIGCHLD`].
+	pub fn from_signals(signals: &[c_int]) -> std::io::Result<Self> {
+		let mut set = Self::new();
+		for &signal in signals {
+			set.insert(signal)?;
+		}
+		Ok(set)
+	}
+
+	/// Add a signal to the set.
+	///
+	/// Fails with `EINVAL` if the signal number is not valid.
+	pub fn insert(&mut self, signal: c_int) -> std::io::Result<()> {
+		unsafe {
+			check_ret(libc::sigaddset(&mut self.inner, signal))?;
+		}
+		Ok(())
+	}
+
+	/// Remove a signal from the set.
+	///
+	/// Fails with `EINVAL` if the signal number is not valid.
+	pub fn remove(&mut self, signal: c_int) -> std::io::Result<()> {
+		unsafe {
+			check_ret(libc::sigdelset(&mut self.inner, signal))?;
+		}
+		Ok(())
+	}
+
+	/// Check if the set contains a signal.
+	///
+	/// Invalid signal numbers are never contained in the set.
+	pub fn contains(&self, signal: c_int) -> bool {
+		unsafe { libc::sigismember(&self.inner, signal) == 1 }
+	}
+
+	/// Get a reference to the raw `sigset_t`.
+	pub fn as_raw(&self) -> &libc::sigset_t {
+		&self.inner
+	}
+}
+
+impl std::fmt::Debug for SignalSet {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let signals = (1..=libc::SIGRTMAX()).filter(|&signal| self.contains(signal));
+		f.debug_set().entries(signals).finish()
+	}
+}
+
+/// Information about a signal, read from a [`SignalFd`].
+#[derive(Copy, Clone)]
+pub struct SignalInfo {
+	inner: libc::signalfd_siginfo,
+}
+
+impl SignalInfo {
+	/// Get a reference to the raw `signalfd_siginfo`.
+	pub fn as_raw(&self) -> &libc::signalfd_siginfo {
+		&self.inner
+	}
+
+	/// Get the signal number.
+	pub fn signal(&self) -> c_int {
+		self.inner.ssi_signo as c_int
+	}
+
+	/// Get the signal code, which describes why the signal was sent (`SI_USER`, `CLD_EXITED`, ...).
+	pub fn code(&self) -> i32 {
+		self.inner.ssi_code
+	}
+
+	/// Get the process ID of the sender of the signal.
+	///
+	/// For `SIGCHLD`, this is the process ID of the child that changed state.
+	pub fn pid(&self) -> u32 {
+		self.inner.ssi_pid
+	}
+
+	/// Get the real user ID of the sender of the signal.
+	pub fn uid(&self) -> u32 {
+		self.inner.ssi_uid
+	}
+
+	/// Get the exit status or signal of the child process, for `SIGCHLD`.
+	///
+	/// If [`Self::code()`] is `CLD_EXITED`, this is the exit status of the child.
+	/// Otherwise, it is the signal that caused the child to change state.
+	pub fn status(&self) -> i32 {
+		self.inner.ssi_status
+	}
+}
+
+impl std::fmt::Debug for SignalInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("SignalInfo")
+			.field("signal", &self.signal())
+			.field("code", &self.code())
+			.field("pid", &self.pid())
+			.field("uid", &self.uid())
+			.field("status", &self.status())
+			.finish_non_exhaustive()
+	}
+}
+
+/// A file descriptor for accepting signals synchronously, created with `signalfd()`.
+///
+/// Signals that are accepted through the file descriptor must be blocked,
+/// otherwise they are handled by their normal disposition.
+/// [`Self::new()`] and [`Self::set_mask()`] block the signals in the calling thread.
+/// Note that a signal directed at the process can be delivered to any thread that does not block it,
+/// so it should be blocked in all threads, for example by creating the [`SignalFd`] before spawning other threads.
+#[derive(Debug)]
+pub struct SignalFd {
+	fd: FileDesc,
+}
+
+impl SignalFd {
+	/// Block the signals in the calling thread, and create a file descriptor to accept them.
+	///
+	/// If `nonblocking` is true, the file descriptor is created with `SFD_NONBLOCK`.
+	///
+	/// The new file descriptor will have the `close-on-exec` flag set atomically.
+	///
+	/// The signals are blocked only after the file descriptor was created successfully,
+	/// so the signal mask of the thread is left unchanged on error.
+	pub fn new(mask: &SignalSet, nonblocking: bool) -> std::io::Result<Self> {
+		let mut flags = libc::SFD_CLOEXEC;
+		if nonblocking {
+			flags |= libc::SFD_NONBLOCK;
+		}
+		let fd = unsafe {
+			let fd = check_ret(libc::signalfd(-1, &mask.inner, flags))?;
+			FileDesc::from_raw_fd(fd)
+		};
+		block_signals(mask)?;
+		Ok(Self::from_file_desc(fd))
+	}
+
+	/// Wrap a [`FileDesc`] that refers to a signal file descriptor.
+	///
+	/// This does not check that the file descriptor refers to a signal file descriptor.
+	/// If it does not, the other functions will fail or behave unexpectedly.
+	/// You can use [`FileDesc::file_type()`] to check the type first.
+	pub fn from_file_desc(fd: FileDesc) -> Self {
+		Self { fd }
+	}
+
+	/// Get a reference to the wrapped [`FileDesc`].
+	pub fn as_file_desc(&self) -> &FileDesc {
+		&self.fd
+	}
+
+	/// Release the wrapped [`FileDesc`].
+	pub fn into_file_desc(self) -> FileDesc {
+		self.fd
+	}
+
+	/// Replace the set of signals accepted by the file descriptor.
+	///
+	/// The new signals are blocked in the calling thread.
+	/// Signals that are removed from the mask are not unblocked.
+	/// If updating the file descriptor fails, the previous signal mask of the thread is restored.
+	pub fn set_mask(&self, mask: &SignalSet) -> std::io::Result<()> {
+		let old_mask = block_signals(mask)?;
+		unsafe {
+			if let Err(e) = check_ret(libc::signalfd(self.fd.as_raw_fd(), &mask.inner, 0)) {
+				let _ = set_thread_mask(libc::SIG_SETMASK, &old_mask);
+				return Err(e);
+			}
+		}
+		Ok(())
+	}
+
+	/// Wait for a signal, and read information about it.
+	///
+	/// In non-blocking mode, this fails with [`std::io::ErrorKind::WouldBlock`] if no signal is pending.
+	pub fn wait(&self) -> std::io::Result<SignalInfo> {
+		read_siginfo(&self.fd)
+	}
+
+	/// Read information about a pending signal, without blocking.
+	///
+	/// Returns `None` if no signal is pending.
+	/// If the file descriptor is in blocking mode, this first checks for pending signals with `poll()`.
+	/// In that case, the call can still block if another thread accepts the signal at the same time.
+	pub fn try_wait(&self) -> std::io::Result<Option<SignalInfo>> {
+		super::io::try_read(&self.fd, read_siginfo)
+	}
+}
+
+/// Block signals in the calling thread, and return the previous signal mask.
+fn block_signals(mask: &SignalSet) -> std::io::Result<SignalSet> {
+	set_thread_mask(libc::SIG_BLOCK, mask)
+}
+
+/// Change the signal mask of the calling thread with `pthread_sigmask()`, and return the previous signal mask.
+fn set_thread_mask(how: c_int, mask: &SignalSet) -> std::io::Result<SignalSet> {
+	let mut old_mask = SignalSet::new();
+	let ret = unsafe { libc::pthread_sigmask(how, &mask.inner, &mut old_mask.inner) };
+	if ret != 0 {
+		return Err(std::io::Error::from_raw_os_error(ret));
+	}
+	Ok(old_mask)
+}
+
+/// Read a `signalfd_siginfo` from a signal file descriptor.
+fn read_siginfo(fd: &FileDesc) -> std::io::Result<SignalInfo> {
+	unsafe {
+		let mut inner: libc::signalfd_siginfo = std::mem::zeroed();
+		let size = std::mem::size_of::<libc::signalfd_siginfo>();
+		let buf = std::slice::from_raw_parts_mut((&mut inner as *mut libc::signalfd_siginfo).cast::<u8>(), size);
+		super::io::read_record(fd, buf)?;
+		Ok(SignalInfo { inner })
+	}
+}
+
+impl_file_desc_wrapper!(SignalFd);
@@ -0,0 +1,372 @@
+use std::os::raw::c_int;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::os::unix::net::{UnixDatagram, UnixStream};
+
+use super::{check_ret, retry_eintr, FileDesc};
+
+/// The type of a Unix socket.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SocketType {
+	/// A connection-oriented byte stream (`SOCK_STREAM`).
+	Stream,
+
+	/// A connectionless socket that preserves message boundaries (`SOCK_DGRAM`).
+	Datagram,
+
+	/// A connection-oriented socket that preserves message boundaries (`SOCK_SEQPACKET`).
+	SeqPacket,
+}
+
+impl SocketType {
+	/// Get the raw socket type for this value.
+	fn to_raw(self) -> c_int {
+		match self {
+			Self::Stream => libc::SOCK_STREAM,
+			Self::Datagram => libc::SOCK_DGRAM,
+			Self::SeqPacket => libc::SOCK_SEQPACKET,
+		}
+	}
+}
+
+/// Options for creating sockets with [`FileDesc::socketpair()`].
+///
+/// The `close-on-exec` flag is always set on the created sockets.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SocketFlags {
+	nonblocking: bool,
+}
+
+impl SocketFlags {
+	/// Create new socket flags with all options disabled.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Put the sockets in non-blocking mode (`O_NONBLOCK`).
+	pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+		self.nonblocking = nonblocking;
+		self
+	}
+}
+
+impl FileDesc {
+	/// Create a pair of connected Unix sockets.
+	///
+	/// Both sockets will have the `close-on-exec` flag set.
+	/// If the platform supports it, the flag will be set atomically using `SOCK_CLOEXEC`.
+	/// Otherwise, the library falls back to setting the flags non-atomically.
+	pub fn socketpair(socket_type: SocketType, flags: SocketFlags) -> std::io::Result<(FileDesc, FileDesc)> {
+		let mut fds = [-1; 2];
+
+		#[cfg(not(target_vendor = "apple"))]
+		unsafe {
+			let mut raw_type = socket_type.to_raw() | libc::SOCK_CLOEXEC;
+			if flags.nonblocking {
+				raw_type |= libc::SOCK_NONBLOCK;
+			}
+			check_ret(libc::socketpair(libc::AF_UNIX, raw_type, 0, fds.as_mut_ptr()))?;
+			Ok((Self::from_raw_fd(fds[0]), Self::from_raw_fd(fds[1])))
+		}
+
+		#[cfg(target_vendor = "apple")]
+		unsafe {
+			check_ret(libc::socketpair(libc::AF_UNIX, socket_type.to_raw(), 0, fds.as_mut_ptr()))?;
+			let pair = (Self::from_raw_fd(fds[0]), Self::from_raw_fd(fds[1]));
+			for fd in [&pair.0, &pair.1] {
+				fd.set_close_on_exec(true)?;
+				if flags.nonblocking {
+					fd.set_nonblocking(true)?;
+				}
+			}
+			Ok(pair)
+		}
+	}
+}
+
+impl From<FileDesc> for UnixStream {
+	/// Convert a [`FileDesc`] into a [`UnixStream`].
+	///
+	/// The file descriptor should be a Unix socket of type [`SocketType::Stream`] or [`SocketType::SeqPacket`].
+	fn from(value: FileDesc) -> Self {
+		value.into_fd().into()
+	}
+}
+
+impl From<FileDesc> for UnixDatagram {
+	/// Convert a [`FileDesc`] into a [`UnixDatagram`].
+	///
+	/// The file descriptor should be a Unix socket of type [`SocketType::Datagram`].
+	fn from(value: FileDesc) -> Self {
+		value.into_fd().into()
+	}
+}
+
+/// Send data and file descriptors over a Unix socket.
+///
+/// The file descriptors are sent as `SCM_RIGHTS` ancillary data with a single `sendmsg()` call.
+/// The receiving process gets new file descriptors that refer to the same open file descriptions.
+///
+/// For stream sockets, `data` should not be empty, since the file descriptors are attached to the data.
+///
+/// Returns the number of bytes of `data` that were sent.
+/// If not all data is sent, the file descriptors are still sent with the first part of the data.
+/// The call is retried if it is interrupted by a signal.
+pub fn send_fds(socket: impl AsFd, data: &[u8], fds: &[BorrowedFd<'_>]) -> std::io::Result<usize> {
+	let fds_len = std::mem::size_of_val(fds);
+	let mut control = ControlBuffer::new(cmsg_space(fds_len));
+
+	let mut iov = libc::iovec {
+		iov_base: data.as_ptr() as *mut libc::c_void,
+		iov_len: data.len(),
+	};
+
+	unsafe {
+		let mut msg: libc::msghdr = std::mem::zeroed();
+		msg.msg_iov = &mut iov;
+		msg.msg_iovlen = 1;
+		if !fds.is_empty() {
+			msg.msg_control = control.as_mut_ptr();
+			msg.msg_controllen = control.len() as _;
+			let cmsg = libc::CMSG_FIRSTHDR(&msg);
+			(*cmsg).cmsg_level = libc::SOL_SOCKET;
+			(*cmsg).cmsg_type = libc::SCM_RIGHTS;
+			(*cmsg).cmsg_len = libc::CMSG_LEN(fds_len as _) as _;
+			// BorrowedFd is guaranteed to have the same layout as RawFd.
+			std::ptr::copy_nonoverlapping(fds.as_ptr().cast::<u8>(), libc::CMSG_DATA(cmsg), fds_len);
+		}
+
+		let sent = retry_eintr(|| check_ret(libc::sendmsg(socket.as_fd().as_raw_fd(), &msg, SEND_FLAGS)))?;
+		Ok(sent as usize)
+	}
+}
+
+/// Receive data and file descriptors from a Unix socket.
+///
+/// Returns the number of bytes read into `buf`, and the received file descriptors.
+/// At most `max_fds` file descriptors can be received.
+///
+/// The received file descriptors will have the `close-on-exec` flag set.
+/// If the platform supports it, the flag will be set atomically using `MSG_CMSG_CLOEXEC`.
+/// Otherwise, the library falls back to setting the flag non-atomically.
+///
+/// If the sender sent more file descriptors than fit in the control buffer,
+/// the kernel discards the remaining file descriptors and sets `MSG_CTRUNC`.
+/// This is reported as an error of kind [`std::io::ErrorKind::InvalidData`].
+/// On any error, all file descriptors that were received are closed.
+///
+/// If the socket has `SO_PASSCRED` enabled, the credentials attached by the kernel are discarded.
+/// Use [`recv_fds_with_credentials()`] to receive them.
+///
+/// The call is retried if it is interrupted by a signal.
+pub fn recv_fds(socket: impl AsFd, buf: &mut [u8], max_fds: usize) -> std::io::Result<(usize, Vec<FileDesc>)> {
+	let (received, fds, _credentials) = recv_msg(socket.as_fd(), buf, max_fds)?;
+	Ok((received, fds))
+}
+
+/// Receive data, file descriptors and the credentials of the sender from a Unix socket.
+///
+/// This is the same as [`recv_fds()`], except that it also receives `SCM_CREDENTIALS` ancillary data in the same `recvmsg()` call.
+/// This ties the credentials of the sender to the received data and file descriptors.
+///
+/// The kernel only attaches credentials if the receiving socket has the `SO_PASSCRED` option enabled.
+/// You can enable it with [`FileDesc::set_pass_credentials()`].
+/// If no credentials were received, `None` is returned for the credentials.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn recv_fds_with_credentials(
+	socket: impl AsFd,
+	buf: &mut [u8],
+	max_fds: usize,
+) -> std::io::Result<(usize, Vec<FileDesc>, Option<Credentials>)> {
+	recv_msg(socket.as_fd(), buf, max_fds)
+}
+
+/// Receive a message with `recvmsg()` and parse the `SCM_RIGHTS` and `SCM_CREDENTIALS` ancillary data.
+fn recv_msg(socket: BorrowedFd<'_>, buf: &mut [u8], max_fds: usize) -> std::io::Result<(usize, Vec<FileDesc>, Option<Credentials>)> {
+	let mut control_len = 0;
+	if max_fds > 0 {
+		control_len += cmsg_space(max_fds * std::mem::size_of::<RawFd>());
+	}
+	// Always reserve space for credentials: if `SO_PASSCRED` is enabled, the kernel attaches them to every message,
+	// and a missing slot would truncate the control data.
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	{
+		control_len += cmsg_space(std::mem::size_of::<libc::ucred>());
+	}
+	let mut control = ControlBuffer::new(control_len);
+
+	let mut iov = libc::iovec {
+		iov_base: buf.as_mut_ptr().cast(),
+		iov_len: buf.len(),
+	};
+
+	unsafe {
+		let mut msg: libc::msghdr = std::mem::zeroed();
+		msg.msg_iov = &mut iov;
+		msg.msg_iovlen = 1;
+		if control_len > 0 {
+			msg.msg_control = control.as_mut_ptr();
+			msg.msg_controllen = control.len() as _;
+		}
+
+		let received = retry_eintr(|| check_ret(libc::recvmsg(socket.as_raw_fd(), &mut msg, RECV_FLAGS)))?;
+
+		// Take ownership of all received file descriptors first, so they are closed on any error.
+		let mut fds = Vec::new();
+		#[allow(unused_mut)]
+		let mut credentials = None;
+		let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+		while !cmsg.is_null() {
+			let data = libc::CMSG_DATA(cmsg);
+			let data_len = (*cmsg).cmsg_len as usize - (data as usize - cmsg as usize);
+			match ((*cmsg).cmsg_level, (*cmsg).cmsg_type) {
+				(libc::SOL_SOCKET, libc::SCM_RIGHTS) => {
+					for i in 0..data_len / std::mem::size_of::<RawFd>() {
+						let fd = std::ptr::read_unaligned(data.cast::<RawFd>().add(i));
+						fds.push(FileDesc::from_raw_fd(fd));
+					}
+				},
+				#[cfg(any(target_os = "linux", target_os = "android"))]
+				(libc::SOL_SOCKET, libc::SCM_CREDENTIALS) if data_len >= std::mem::size_of::<libc::ucred>() => {
+					credentials = Some(Credentials::from_ucred(std::ptr::read_unaligned(data.cast())));
+				},
+				_ => (),
+			}
+			cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+		}
+
+		if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				"control message truncated: received more file descriptors than requested",
+			));
+		}
+
+		#[cfg(target_vendor = "apple")]
+		for fd in &fds {
+			fd.set_close_on_exec(true)?;
+		}
+
+		Ok((received as usize, fds, credentials))
+	}
+}
+
+/// Credentials of a process connected to a Unix socket.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Credentials {
+	/// The process ID.
+	pub pid: libc::pid_t,
+
+	/// The user ID.
+	pub uid: libc::uid_t,
+
+	/// The group ID.
+	pub gid: libc::gid_t,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl Credentials {
+	/// Convert a `struct ucred` into [`Credentials`].
+	fn from_ucred(ucred: libc::ucred) -> Self {
+		Self {
+			pid: ucred.pid,
+			uid: ucred.uid,
+			gid: ucred.gid,
+		}
+	}
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl FileDesc {
+	/// Get the credentials of the peer of a connected Unix socket (`SO_PEERCRED`).
+	///
+	/// The credentials are those of the process that connected the socket or created the socket pair,
+	/// at the time of the `connect()` or `socketpair()` call.
+	/// The process ID may have been reused by a different process since then.
+	/// Use [`Self::peer_pidfd()`] to refer to the peer process reliably.
+	pub fn peer_credentials(&self) -> std::io::Result<Credentials> {
+		let ucred: libc::ucred = self.getsockopt(libc::SOL_SOCKET, libc::SO_PEERCRED)?;
+		Ok(Credentials::from_ucred(ucred))
+	}
+
+	/// Get a process file descriptor for the peer of a connected Unix socket (`SO_PEERPIDFD`).
+	///
+	/// This requires Linux 6.5 or later.
+	/// The returned file descriptor will have the `close-on-exec` flag set.
+	#[cfg(target_os = "linux")]
+	pub fn peer_pidfd(&self) -> std::io::Result<FileDesc> {
+		let fd: c_int = self.getsockopt(libc::SOL_SOCKET, libc::SO_PEERPIDFD)?;
+		// The kernel always creates the pidfd with the close-on-exec flag set.
+		Ok(unsafe { FileDesc::from_raw_fd(fd) })
+	}
+
+	/// Enable or disable receiving credentials as ancillary data on a Unix socket (`SO_PASSCRED`).
+	///
+	/// When enabled, every message received on the socket carries the credentials of the sender.
+	/// They can be received with [`recv_fds_with_credentials()`].
+	pub fn set_pass_credentials(&self, enable: bool) -> std::io::Result<()> {
+		self.setsockopt(libc::SOL_SOCKET, libc::SO_PASSCRED, c_int::from(enable))
+	}
+
+	/// Get a socket option with `getsockopt()`.
+	fn getsockopt<T: Copy>(&self, level: c_int, name: c_int) -> std::io::Result<T> {
+		unsafe {
+			let mut value = std::mem::MaybeUninit::<T>::zeroed();
+			let mut len = std::mem::size_of::<T>() as libc::socklen_t;
+			check_ret(libc::getsockopt(self.as_raw_fd(), level, name, value.as_mut_ptr().cast(), &mut len))?;
+			Ok(value.assume_init())
+		}
+	}
+
+	/// Set a socket option with `setsockopt()`.
+	fn setsockopt<T: Copy>(&self, level: c_int, name: c_int, value: T) -> std::io::Result<()> {
+		unsafe {
+			let len = std::mem::size_of::<T>() as libc::socklen_t;
+			check_ret(libc::setsockopt(self.as_raw_fd(), level, name, (&value as *const T).cast(), len))?;
+			Ok(())
+		}
+	}
+}
+
+/// Flags for `sendmsg()`: do not raise `SIGPIPE` when the peer has closed the connection, if supported.
+#[cfg(not(target_vendor = "apple"))]
+const SEND_FLAGS: c_int = libc::MSG_NOSIGNAL;
+#[cfg(target_vendor = "apple")]
+const SEND_FLAGS: c_int = 0;
+
+/// Flags for `recvmsg()`: set the `close-on-exec` flag on received file descriptors atomically, if supported.
+#[cfg(not(target_vendor = "apple"))]
+const RECV_FLAGS: c_int = libc::MSG_CMSG_CLOEXEC;
+#[cfg(target_vendor = "apple")]
+const RECV_FLAGS: c_int = 0;
+
+/// Get the space needed for a control message with `data_len` bytes of payload, including padding.
+fn cmsg_space(data_len: usize) -> usize {
+	unsafe { libc::CMSG_SPACE(data_len as _) as usize }
+}
+
+/// Buffer for ancillary data, suitably aligned for `struct cmsghdr`.
+struct ControlBuffer {
+	data: Vec<libc::cmsghdr>,
+	len: usize,
+}
+
+impl ControlBuffer {
+	/// Create a zeroed buffer of `len` bytes.
+	fn new(len: usize) -> Self {
+		let elements = len.div_ceil(std::mem::size_of::<libc::cmsghdr>());
+		Self {
+			data: vec![unsafe { std::mem::zeroed() }; elements],
+			len,
+		}
+	}
+
+	/// Get a pointer to the start of the buffer.
+	fn as_mut_ptr(&mut self) -> *mut libc::c_void {
+		self.data.as_mut_ptr().cast()
+	}
+
+	/// Get the length of the buffer in bytes.
+	fn len(&self) -> usize {
+		self.len
+	}
+}
@@ -0,0 +1,192 @@
+use std::os::raw::c_int;
+use std::time::Duration;
+
+use super::{check_ret, FileDesc};
+
+/// The clock used by a [`TimerFd`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Clock {
+	/// A clock that is not affected by changes to the system time, and does not count time while the system is suspended (`CLOCK_MONOTONIC`).
+	Monotonic,
+
+	/// The system time (`CLOCK_REALTIME`).
+	///
+	/// Absolute deadlines for this clock are measured since the Unix epoch.
+	Realtime,
+
+	/// Like [`Self::Monotonic`], but it also counts time while the system is suspended (`CLOCK_BOOTTIME`).
+	Boottime,
+}
+
+impl Clock {
+	/// Get the raw clock ID.
+	fn to_raw(self) -> libc::clockid_t {
+		match self {
+			Self::Monotonic => libc::CLOCK_MONOTONIC,
+			Self::Realtime => libc::CLOCK_REALTIME,
+			Self::Boottime => libc::CLOCK_BOOTTIME,
+		}
+	}
+}
+
+/// A timer that notifies through a file descriptor, created with `timerfd_create()`.
+///
+/// When the timer expires, the file descriptor becomes readable,
+/// and reading it with [`Self::wait()`] returns the number of expirations since the last read.
+///
+/// The file descriptor can be polled together with other file descriptors, or passed to other processes.
+#[derive(Debug)]
+pub struct TimerFd {
+	fd: FileDesc,
+}
+
+impl TimerFd {
+	/// Create a new, disarmed timer.
+	///
+	/// If `nonblocking` is true, the timer is created with `TFD_NONBLOCK`.
+	///
+	/// The new file descriptor will have the `close-on-exec` flag set atomically.
+	pub fn new(clock: Clock, nonblocking: bool) -> std::io::Result<Self> {
+		let mut flags = libc::TFD_CLOEXEC;
+		if nonblocking {
+			flags |= libc::TFD_NONBLOCK;
+		}
+		unsafe {
+			let fd = check_ret(libc::timerfd_create(clock.to_raw(), flags))?;
+			Ok(Self::from_file_desc(FileDesc::from_raw_fd(fd)))
+		}
+	}
+
+	/// Wrap a [`FileDesc`] that refers to a timer, for example one received with [`crate::recv_fds()`].
+	///
+	/// This does not check that the file descriptor refers to a timer.
+	/// If it does not, the other functions will fail or behave unexpectedly.
+	/// You can use [`FileDesc::file_type()`] to check the type first.
+	pub fn from_file_desc(fd: FileDesc) -> Self {
+		Self { fd }
+	}
+
+	/// Get a reference to the wrapped [`FileDesc`].
+	pub fn as_file_desc(&self) -> &FileDesc {
+		&self.fd
+	}
+
+	/// Release the wrapped [`FileDesc`].
+	pub fn into_file_desc(self) -> FileDesc {
+		self.fd
+	}
+
+	/// Arm the timer to expire after `timeout`, and then every `interval` if it is given.
+	///
+	/// A timeout of zero makes the timer expire as soon as possible.
+	/// This replaces any previous setting of the timer and resets the expiration count.
+	pub fn arm(&self, timeout: Duration, interval: Option<Duration>) -> std::io::Result<()> {
+		self.set_time(0, timeout, interval)
+	}
+
+	/// Arm the timer to expire at an absolute `deadline`, and then every `interval` if it is given.
+	///
+	/// The deadline is measured on the clock of the timer.
+	/// For [`Clock::Realtime`] this is the time since the Unix epoch,
+	/// for the other clocks it is an unspecified starting point, usually the boot time of the system.
+	/// A deadline in the past makes the timer expire as soon as possible.
+	///
+	/// If `cancel_on_set` is true, the timer is armed with `TFD_TIMER_CANCEL_ON_SET`.
+	/// Then, if the system time is changed discontinuously, waiting for the timer fails with `ECANCELED`.
+	/// This only has an effect for timers that use [`Clock::Realtime`].
+	pub fn arm_absolute(&self, deadline: Duration, interval: Option<Duration>, cancel_on_set: bool) -> std::io::Result<()> {
+		let mut flags = libc::TFD_TIMER_ABSTIME;
+		if cancel_on_set {
+			flags |= libc::TFD_TIMER_CANCEL_ON_SET;
+		}
+		self.set_time(flags, deadline, interval)
+	}
+
+	/// Disarm the timer.
+	pub fn disarm(&self) -> std::io::Result<()> {
+		let value = libc::itimerspec {
+			it_value: zero_timespec(),
+			it_interval: zero_timespec(),
+		};
+		self.set_raw(0, &value)
+	}
+
+	/// Get the time until the next expiration of the timer, or `None` if the timer is disarmed.
+	pub fn remaining(&self) -> std::io::Result<Option<Duration>> {
+		let value = self.get_raw()?;
+		Ok(Some(from_timespec(value.it_value)).filter(|remaining| !remaining.is_zero()))
+	}
+
+	/// Get the interval of the timer, or `None` if the timer is one-shot or disarmed.
+	pub fn interval(&self) -> std::io::Result<Option<Duration>> {
+		let value = self.get_raw()?;
+		Ok(Some(from_timespec(value.it_interval)).filter(|interval| !interval.is_zero()))
+	}
+
+	/// Wait until the timer expires, and read the number of expirations since the timer was armed or last read.
+	///
+	/// In non-blocking mode, this fails with [`std::io::ErrorKind::WouldBlock`] if the timer has not expired.
+	/// If the timer was armed with `cancel_on_set` and the system time changed, this fails with `ECANCELED`.
+	pub fn wait(&self) -> std::io::Result<u64> {
+		super::io::read_u64(&self.fd)
+	}
+
+	/// Read the number of expirations if the timer expired, without blocking.
+	///
+	/// Returns `None` if the timer has not expired.
+	/// If the file descriptor is in blocking mode, this first checks the timer with `poll()`.
+	/// In that case, the call can still block if another thread or process reads the timer at the same time.
+	pub fn try_wait(&self) -> std::io::Result<Option<u64>> {
+		super::io::try_read_u64(&self.fd)
+	}
+
+	/// Arm the timer with `timerfd_settime()`.
+	fn set_time(&self, flags: c_int, value: Duration, interval: Option<Duration>) -> std::io::Result<()> {
+		// A zero value disarms the timer, so use the smallest non-zero value instead.
+		let value = value.max(Duration::from_nanos(1));
+		let value = libc::itimerspec {
+			it_value: to_timespec(value)?,
+			it_interval: to_timespec(interval.unwrap_or(Duration::ZERO))?,
+		};
+		self.set_raw(flags, &value)
+	}
+
+	/// Call `timerfd_settime()` with a raw value.
+	fn set_raw(&self, flags: c_int, value: &libc::itimerspec) -> std::io::Result<()> {
+		unsafe {
+			check_ret(libc::timerfd_settime(self.fd.as_raw_fd(), flags, value, std::ptr::null_mut()))?;
+		}
+		Ok(())
+	}
+
+	/// Call `timerfd_gettime()`.
+	fn get_raw(&self) -> std::io::Result<libc::itimerspec> {
+		unsafe {
+			let mut value = std::mem::zeroed();
+			check_ret(libc::timerfd_gettime(self.fd.as_raw_fd(), &mut value))?;
+			Ok(value)
+		}
+	}
+}
+
+/// Get a zero `timespec`.
+fn zero_timespec() -> libc::timespec {
+	libc::timespec { tv_sec: 0, tv_nsec: 0 }
+}
+
+/// Convert a [`Duration`] to a `timespec`, failing if it does not fit.
+fn to_timespec(duration: Duration) -> std::io::Result<libc::timespec> {
+	let tv_sec = libc::time_t::try_from(duration.as_secs())
+		.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "duration too large"))?;
+	Ok(libc::timespec {
+		tv_sec,
+		tv_nsec: duration.subsec_nanos() as _,
+	})
+}
+
+/// Convert a `timespec` to a [`Duration`].
+fn from_timespec(value: libc::timespec) -> Duration {
+	Duration::new(value.tv_sec as u64, value.tv_nsec as u32)
+}
+
+impl_file_desc_wrapper!(TimerFd);
@@ -0,0 +1,208 @@
+use std::ffi::{OsStr, OsString};
+use std::hash::{BuildHasher, Hasher};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::path::Path;
+
+use super::{check_ret, Dir, FileDesc, OpenFlags, RenameFlags};
+
+/// The number of attempts to find an unused random file name.
+const MAX_NAME_ATTEMPTS: usize = 100;
+
+/// A temporary file, created with [`FileDesc::tmpfile_in()`].
+///
+/// The file is removed when it is dropped, unless it is given a name with [`Self::persist()`].
+#[derive(Debug)]
+pub struct TempFile {
+	fd: FileDesc,
+
+	/// The directory and name of the file, if the file system did not support `O_TMPFILE`.
+	named: Option<TempName>,
+}
+
+/// The name of a named temporary file, which is removed on drop unless the file was persisted.
+#[derive(Debug)]
+struct TempName {
+	dir: Dir,
+	name: OsString,
+	persisted: bool,
+}
+
+impl FileDesc {
+	/// Create an anonymous temporary file in a directory.
+	///
+	/// On Linux, the file is created with `O_TMPFILE`, so it has no name and disappears automatically when it is closed.
+	/// It can be given a name later with [`TempFile::persist()`].
+	///
+	/// If the file system does not support `O_TMPFILE` (or on other platforms),
+	/// the library falls back to creating a file with a random hidden name.
+	/// An unlinked file can not be given a name again without copying it,
+	/// so the file keeps its random name until it is persisted.
+	/// It is removed when the [`TempFile`] is dropped, but it may be left behind if the process is killed.
+	///
+	/// The file is opened for reading and writing with permissions `0o600`,
+	/// and the file descriptor will have the `close-on-exec` flag set atomically.
+	pub fn tmpfile_in(dir: &Dir) -> std::io::Result<TempFile> {
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		{
+			let flags = OpenFlags::new().write(true).tmpfile(true).mode(0o600);
+			match dir.open_file(".", flags) {
+				Ok(fd) => return Ok(TempFile { fd, named: None }),
+				Err(e) if !matches!(e.raw_os_error(), Some(libc::EOPNOTSUPP) | Some(libc::EISDIR) | Some(libc::EINVAL)) => return Err(e),
+				Err(_) => (),
+			}
+		}
+		Self::named_tmpfile_in(dir)
+	}
+
+	/// Create a temporary file with a random hidden name.
+	///
+	/// This is the fallback of [`Self::tmpfile_in()`] for file systems without `O_TMPFILE`.
+	pub(crate) fn named_tmpfile_in(dir: &Dir) -> std::io::Result<TempFile> {
+		let flags = OpenFlags::new().write(true).create_new(true).mode(0o600);
+		let (fd, name) = with_random_name(|name| dir.open_file(name, flags))?;
+		let dir = Dir::new(dir.as_file_desc().duplicate()?);
+		Ok(TempFile {
+			fd,
+			named: Some(TempName {
+				dir,
+				name,
+				persisted: false,
+			}),
+		})
+	}
+}
+
+impl TempFile {
+	/// Get a reference to the wrapped [`FileDesc`].
+	pub fn as_file_desc(&self) -> &FileDesc {
+		&self.fd
+	}
+
+	/// Give the temporary file a name, and release the wrapped [`FileDesc`].
+	///
+	/// The file is linked into `dir` as `name`.
+	/// If `replace` is true, an existing file with the same name is atomically replaced.
+	/// Otherwise, the operation fails with an error of kind [`std::io::ErrorKind::AlreadyExists`] if the name is taken.
+	///
+	/// For unnamed files, the file is linked with `linkat()` and `AT_EMPTY_PATH`,
+	/// falling back to linking `/proc/self/fd/N` if that is not permitted.
+	/// Replacing an existing file is done by linking the file under a random name first, and then renaming it.
+	/// Files with a random name are renamed to `name` directly.
+	///
+	/// Refusing to replace an existing file uses `renameat2()` with `RENAME_NOREPLACE`.
+	/// If that is not supported by the platform or the file system,
+	/// the file is hard linked to `name` and the random name is removed.
+	///
+	/// The data of the file is not flushed to the storage device.
+	/// Call [`FileDesc::sync_all()`] first if you need that.
+	/// On error, the temporary file is removed.
+	pub fn persist(self, dir: &Dir, name: impl AsRef<Path>, replace: bool) -> std::io::Result<FileDesc> {
+		let name = name.as_ref();
+		match self.named {
+			Some(mut temp) => {
+				// On error, the temporary name is removed when `temp` is dropped.
+				rename_temp(&temp.dir, &temp.name, dir, name, replace)?;
+				temp.persisted = true;
+			},
+			None => {
+				if replace {
+					let (_, temp_name) = with_random_name(|temp_name| self.link_into(dir, temp_name))?;
+					if let Err(e) = rename_temp(dir, &temp_name, dir, name, true) {
+						let _ = dir.remove_file(&temp_name);
+						return Err(e);
+					}
+				} else {
+					self.link_into(dir, name)?;
+				}
+			},
+		}
+		Ok(self.fd)
+	}
+
+	/// Link an unnamed temporary file into a directory.
+	fn link_into(&self, dir: &Dir, name: &Path) -> std::io::Result<()> {
+		let name = super::open::path_to_cstring(name)?;
+
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		unsafe {
+			let ret = libc::linkat(self.fd.as_raw_fd(), c"".as_ptr(), dir.as_raw_fd(), name.as_ptr(), libc::AT_EMPTY_PATH);
+			match check_ret(ret) {
+				Ok(_) => return Ok(()),
+				// AT_EMPTY_PATH requires CAP_DAC_READ_SEARCH, so fall back to the /proc magic link.
+				Err(e) if matches!(e.raw_os_error(), Some(libc::ENOENT) | Some(libc::EPERM)) => (),
+				Err(e) => return Err(e),
+			}
+		}
+
+		let proc_path = super::open::path_to_cstring(Path::new(&format!("/proc/self/fd/{}", self.fd.as_raw_fd())))?;
+		unsafe {
+			check_ret(libc::linkat(libc::AT_FDCWD, proc_path.as_ptr(), dir.as_raw_fd(), name.as_ptr(), libc::AT_SYMLINK_FOLLOW))?;
+		}
+		Ok(())
+	}
+}
+
+/// Move a file from its temporary name to its final name.
+///
+/// Without `replace`, this uses `RENAME_NOREPLACE`,
+/// and falls back to `link()` and `unlink()` if the platform or file system does not support it.
+/// On error, the temporary name is left in place.
+fn rename_temp(temp_dir: &Dir, temp_name: &OsStr, dir: &Dir, name: &Path, replace: bool) -> std::io::Result<()> {
+	match temp_dir.rename(temp_name, dir, name, RenameFlags::new().no_replace(!replace)) {
+		// NFS and some other file systems reject RENAME_NOREPLACE with EINVAL.
+		Err(e) if !replace && (e.kind() == std::io::ErrorKind::Unsupported || matches!(e.raw_os_error(), Some(libc::EINVAL) | Some(libc::ENOSYS))) => {
+			temp_dir.hard_link(temp_name, dir, name)?;
+			let _ = temp_dir.remove_file(temp_name);
+			Ok(())
+		},
+		result => result,
+	}
+}
+
+impl Drop for TempName {
+	fn drop(&mut self) {
+		if !self.persisted {
+			let _ = self.dir.remove_file(&self.name);
+		}
+	}
+}
+
+impl AsFd for TempFile {
+	fn as_fd(&self) -> BorrowedFd<'_> {
+		self.fd.as_fd()
+	}
+}
+
+impl AsRawFd for TempFile {
+	fn as_raw_fd(&self) -> RawFd {
+		self.fd.as_raw_fd()
+	}
+}
+
+/// Try an operation with random hidden file names until it does not fail with `EEXIST`.
+pub(crate) fn with_random_name<T, F>(mut f: F) -> std::io::Result<(T, OsString)>
+where
+	F: FnMut(&Path) -> std::io::Result<T>,
+{
+	for _ in 0..MAX_NAME_ATTEMPTS {
+		let name = random_name();
+		match f(Path::new(&name)) {
+			Ok(value) => return Ok((value, name)),
+			Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+			Err(e) => return Err(e),
+		}
+	}
+	Err(std::io::Error::new(
+		std::io::ErrorKind::AlreadyExists,
+		"failed to find an unused name for a temporary file",
+	))
+}
+
+/// Generate a random hidden file name.
+fn random_name() -> OsString {
+	// RandomState is seeded randomly for every instance.
+	let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+	hasher.write_u32(std::process::id());
+	let random = hasher.finish();
+	OsStr::new(&format!(".tmp{random:016x}")).to_owned()
+}
@@ -0,0 +1,276 @@
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+use super::{Dir, DirEntry, FileType, InodeKey, OpenFlags};
+
+/// Options for [`Dir::walk()`] and [`Dir::remove_tree()`].
+#[derive(Debug, Copy, Clone)]
+pub struct WalkOptions {
+	max_depth: Option<usize>,
+	same_file_system: bool,
+	max_open_fds: usize,
+}
+
+impl Default for WalkOptions {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl WalkOptions {
+	/// Create new walk options without depth limit, that cross file system boundaries, with at most 32 open directories.
+	pub fn new() -> Self {
+		Self {
+			max_depth: None,
+			same_file_system: false,
+			max_open_fds: 32,
+		}
+	}
+
+	/// Limit the depth of the walk.
+	///
+	/// The entries of the starting directory have depth 1.
+	/// Directories at the maximum depth are visited, but not descended into.
+	/// A maximum depth of `Some(0)` means that no entries are visited at all.
+	pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+		self.max_depth = max_depth;
+		self
+	}
+
+	/// Do not descend into directories on a different file system than the starting directory.
+	///
+	/// Directories are compared by the `st_dev` field of their metadata.
+	pub fn same_file_system(mut self, same_file_system: bool) -> Self {
+		self.same_file_system = same_file_system;
+		self
+	}
+
+	/// Limit the number of directory file descriptors that the walker keeps open at the same time.
+	///
+	/// When the limit is reached, the file descriptors of ancestor directories are closed.
+	/// They are opened again with `..` when the walker returns to them,
+	/// and the walk fails if the reopened directory is not the same directory as before.
+	///
+	/// The limit does not include the starting directory, and it is at least 1.
+	pub fn max_open_fds(mut self, max_open_fds: usize) -> Self {
+		self.max_open_fds = max_open_fds.max(1);
+		self
+	}
+}
+
+/// An entry visited by [`Dir::walk()`].
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+	path: PathBuf,
+	name: OsString,
+	depth: usize,
+	ino: u64,
+	file_type: FileType,
+}
+
+impl WalkEntry {
+	/// Get the path of the entry relative to the starting directory.
+	///
+	/// The path is only informational: the walker itself never uses it for system calls.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Get the file name of the entry, relative to the directory that contains it.
+	pub fn file_name(&self) -> &OsStr {
+		&self.name
+	}
+
+	/// Get the depth of the entry, where the entries of the starting directory have depth 1.
+	pub fn depth(&self) -> usize {
+		self.depth
+	}
+
+	/// Get the inode number of the entry.
+	pub fn ino(&self) -> u64 {
+		self.ino
+	}
+
+	/// Get the file type of the entry.
+	///
+	/// Symbolic links are reported as [`FileType::Symlink`] and are never followed.
+	pub fn file_type(&self) -> FileType {
+		self.file_type
+	}
+}
+
+impl Dir {
+	/// Recursively walk the directory tree.
+	///
+	/// The visitor is called for every entry before the walker descends into it (pre-order),
+	/// with the directory that contains the entry.
+	/// Use [`WalkEntry::file_name()`] to operate on the entry relative to that directory.
+	/// The visitor can return `false` to skip descending into a directory.
+	///
+	/// The walker opens every subdirectory with `openat()` and `O_NOFOLLOW | O_DIRECTORY`,
+	/// so it never follows symbolic links, even if a directory is replaced by a symbolic link during the walk.
+	/// If the visitor returns an error, the walk is aborted and the error is returned.
+	pub fn walk<F>(&self, options: WalkOptions, mut visitor: F) -> std::io::Result<()>
+	where
+		F: FnMut(&Dir, &WalkEntry) -> std::io::Result<bool>,
+	{
+		walk(self, options, &mut visitor, &mut |_, _| Ok(()))
+	}
+
+	/// Recursively remove a file or directory tree relative to the directory, like `rm -rf`.
+	///
+	/// If `path` refers to a directory, all its contents are removed and then the directory itself.
+	/// Otherwise, the file or symbolic link is removed.
+	///
+	/// The removal walks the tree with file descriptors and `O_NOFOLLOW`,
+	/// so replacing a directory with a symbolic link during the removal can not redirect it to files outside of the tree.
+	///
+	/// If [`WalkOptions::same_file_system()`] or [`WalkOptions::max_depth()`] prevent the walker from descending into a directory,
+	/// removing that directory fails.
+	pub fn remove_tree(&self, path: impl AsRef<Path>, options: WalkOptions) -> std::io::Result<()> {
+		let path = path.as_ref();
+		let dir = match self.open_file(path, OpenFlags::new().directory(true).nofollow(true)) {
+			Ok(fd) => Dir::new(fd),
+			Err(e) if matches!(e.raw_os_error(), Some(libc::ENOTDIR) | Some(libc::ELOOP)) => return self.remove_file(path),
+			Err(e) => return Err(e),
+		};
+
+		walk(
+			&dir,
+			options,
+			&mut |parent, entry| {
+				if entry.file_type() == FileType::Directory {
+					Ok(true)
+				} else {
+					parent.remove_file(entry.file_name())?;
+					Ok(false)
+				}
+			},
+			&mut |parent, entry| parent.remove_dir(entry.file_name()),
+		)?;
+		drop(dir);
+		self.remove_dir(path)
+	}
+}
+
+/// A directory on the stack of the walker.
+struct Frame {
+	/// The directory, or `None` if it was closed to stay below the file descriptor limit (or if this is the starting directory).
+	dir: Option<Dir>,
+
+	/// The identity of the directory, to verify it when it is opened again.
+	key: InodeKey,
+
+	/// The remaining entries of the directory.
+	entries: std::vec::IntoIter<DirEntry>,
+
+	/// The entry of the directory in its parent, or `None` for the starting directory.
+	entry: Option<WalkEntry>,
+}
+
+/// Type of the callbacks used by the walker.
+type Callback<'a, T> = dyn FnMut(&Dir, &WalkEntry) -> std::io::Result<T> + 'a;
+
+/// Walk a directory tree, calling `pre` before descending into an entry, and `post` after leaving a directory.
+fn walk(root: &Dir, options: WalkOptions, pre: &mut Callback<'_, bool>, post: &mut Callback<'_, ()>) -> std::io::Result<()> {
+	if options.max_depth == Some(0) {
+		return Ok(());
+	}
+
+	let root_stat = root.as_file_desc().stat()?;
+	let mut stack = vec![Frame {
+		dir: None,
+		key: root_stat.inode_key(),
+		entries: read_entries(root)?,
+		entry: None,
+	}];
+
+	while let Some(top) = stack.last_mut() {
+		let Some(dir_entry) = top.entries.next() else {
+			let frame = stack.pop().unwrap();
+			let (Some(entry), Some(dir)) = (frame.entry, frame.dir) else {
+				continue;
+			};
+
+			// Make sure the parent directory is open again before calling `post`.
+			let parent_index = stack.len() - 1;
+			if parent_index > 0 && stack[parent_index].dir.is_none() {
+				let parent = dir.open_file("..", OpenFlags::new().directory(true).nofollow(true))?;
+				if parent.inode_key()? != stack[parent_index].key {
+					return Err(std::io::Error::other("directory was moved during the walk"));
+				}
+				stack[parent_index].dir = Some(Dir::new(parent));
+			}
+			drop(dir);
+			post(frame_dir(root, &stack, parent_index), &entry)?;
+			continue;
+		};
+
+		let current_index = stack.len() - 1;
+		let current = frame_dir(root, &stack, current_index);
+		let parent_path = stack[current_index].entry.as_ref().map(|entry| entry.path.as_path()).unwrap_or(Path::new(""));
+		let file_type = match dir_entry.file_type() {
+			Some(file_type) => file_type,
+			None => current.stat(dir_entry.file_name(), false)?.file_type(),
+		};
+		let entry = WalkEntry {
+			path: parent_path.join(dir_entry.file_name()),
+			name: dir_entry.file_name().to_owned(),
+			depth: current_index + 1,
+			ino: dir_entry.ino(),
+			file_type,
+		};
+
+		let descend = pre(current, &entry)?;
+		if !descend || file_type != FileType::Directory || options.max_depth.is_some_and(|max| entry.depth >= max) {
+			continue;
+		}
+
+		let child = Dir::new(current.open_file(entry.file_name(), OpenFlags::new().directory(true).nofollow(true))?);
+		let child_stat = child.as_file_desc().stat()?;
+		if options.same_file_system && child_stat.dev() != root_stat.dev() {
+			continue;
+		}
+
+		let entries = read_entries(&child)?;
+		stack.push(Frame {
+			dir: Some(child),
+			key: child_stat.inode_key(),
+			entries,
+			entry: Some(entry),
+		});
+
+		// Close the oldest ancestors if there are too many open directories.
+		let mut open = stack.iter().filter(|frame| frame.dir.is_some()).count();
+		for frame in stack.iter_mut() {
+			if open <= options.max_open_fds {
+				break;
+			}
+			if frame.dir.take().is_some() {
+				open -= 1;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+/// Get the directory of the frame at the given index.
+///
+/// # Panics
+/// Panics if the directory of the frame is closed.
+fn frame_dir<'a>(root: &'a Dir, stack: &'a [Frame], index: usize) -> &'a Dir {
+	if index == 0 {
+		root
+	} else {
+		stack[index].dir.as_ref().expect("directory of current frame is closed")
+	}
+}
+
+/// Read all entries of a directory.
+///
+/// The entries are read up front, so that the directory file descriptor can be closed while walking subdirectories.
+fn read_entries(dir: &Dir) -> std::io::Result<std::vec::IntoIter<DirEntry>> {
+	let entries: Vec<_> = dir.read_dir()?.collect::<std::io::Result<_>>()?;
+	Ok(entries.into_iter())
+}
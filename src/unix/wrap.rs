@@ -0,0 +1,215 @@
+use std::os::unix::io::{FromRawFd, IntoRawFd, OwnedFd, RawFd};
+
+use super::{check_ret, AccessMode, FileDesc, FileType};
+
+/// Options for validating a raw file descriptor with [`FileDesc::try_from_raw_fd()`].
+///
+/// By default, the file descriptor only has to be open and not be one of the standard I/O streams.
+#[derive(Debug, Clone, Default)]
+pub struct WrapOptions {
+	allow_stdio: bool,
+	file_type: Option<FileType>,
+	access_mode: Option<AccessMode>,
+}
+
+impl WrapOptions {
+	/// Create new options with the default checks.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Allow or reject wrapping file descriptor 0, 1 and 2 (standard input, output and error).
+	///
+	/// By default, the standard I/O streams are rejected.
+	pub fn allow_stdio(mut self, allow: bool) -> Self {
+		self.allow_stdio = allow;
+		self
+	}
+
+	/// Require the file descriptor to refer to a specific type of file.
+	///
+	/// The file type is determined with [`FileDesc::file_type()`], which recognizes special files like memory files on Linux.
+	/// A special file also satisfies the requirement for the file type reported by `fstat()`,
+	/// so a memory file ([`FileType::MemFd`]) is accepted when [`FileType::Regular`] is required.
+	pub fn file_type(mut self, file_type: FileType) -> Self {
+		self.file_type = Some(file_type);
+		self
+	}
+
+	/// Require the file descriptor to allow a specific type of access.
+	///
+	/// A file descriptor opened with [`AccessMode::ReadWrite`] satisfies the requirement for
+	/// [`AccessMode::ReadOnly`] and [`AccessMode::WriteOnly`] too.
+	pub fn access_mode(mut self, access_mode: AccessMode) -> Self {
+		self.access_mode = Some(access_mode);
+		self
+	}
+}
+
+/// Error returned when a raw file descriptor is rejected by [`FileDesc::try_from_raw_fd()`].
+#[derive(Debug)]
+pub struct WrapError {
+	fd: RawFd,
+	kind: WrapErrorKind,
+}
+
+/// The reason why a raw file descriptor was rejected.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum WrapErrorKind {
+	/// The file descriptor is negative.
+	Negative,
+
+	/// The file descriptor is not open.
+	NotOpen,
+
+	/// The file descriptor is one of the standard I/O streams and those were not allowed.
+	Stdio,
+
+	/// The file descriptor refers to the wrong type of file.
+	FileType {
+		/// The required file type.
+		expected: FileType,
+
+		/// The actual file type.
+		actual: FileType,
+	},
+
+	/// The file descriptor does not allow the required access mode.
+	AccessMode {
+		/// The required access mode.
+		expected: AccessMode,
+
+		/// The actual access mode.
+		actual: AccessMode,
+	},
+
+	/// An I/O error occurred while inspecting the file descriptor.
+	Io(std::io::Error),
+}
+
+impl WrapError {
+	/// Get the raw file descriptor that was rejected.
+	pub fn fd(&self) -> RawFd {
+		self.fd
+	}
+
+	/// Get the reason why the file descriptor was rejected.
+	pub fn kind(&self) -> &WrapErrorKind {
+		&self.kind
+	}
+}
+
+impl std::fmt::Display for WrapError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.kind {
+			WrapErrorKind::Negative => write!(f, "invalid file descriptor {}: file descriptors can not be negative", self.fd),
+			WrapErrorKind::NotOpen => write!(f, "invalid file descriptor {}: file descriptor is not open", self.fd),
+			WrapErrorKind::Stdio => write!(f, "invalid file descriptor {}: refusing to wrap a standard I/O stream", self.fd),
+			WrapErrorKind::FileType { expected, actual } => {
+				write!(f, "invalid file descriptor {}: expected file type {expected:?}, got {actual:?}", self.fd)
+			},
+			WrapErrorKind::AccessMode { expected, actual } => {
+				write!(f, "invalid file descriptor {}: expected access mode {expected:?}, got {actual:?}", self.fd)
+			},
+			WrapErrorKind::Io(e) => write!(f, "failed to inspect file descriptor {}: {e}", self.fd),
+		}
+	}
+}
+
+impl std::error::Error for WrapError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match &self.kind {
+			WrapErrorKind::Io(e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
+impl From<WrapError> for std::io::Error {
+	fn from(value: WrapError) -> Self {
+		match value.kind {
+			WrapErrorKind::Io(e) => e,
+			WrapErrorKind::Negative | WrapErrorKind::NotOpen => Self::new(std::io::ErrorKind::InvalidInput, value),
+			_ => Self::new(std::io::ErrorKind::InvalidData, value),
+		}
+	}
+}
+
+impl FileDesc {
+	/// Wrap a raw file descriptor in a [`FileDesc`] after validating it.
+	///
+	/// This is intended for file descriptors that come from an untrusted source,
+	/// like an environment variable or a configuration file.
+	/// The file descriptor must be open and must pass the checks configured in `options`.
+	/// If a check fails, a [`WrapError`] is returned that explains why the file descriptor was rejected.
+	///
+	/// Like [`Self::from_raw_fd()`], this does not set the `close-on-exec` flag.
+	///
+	/// If you already own the file descriptor as an [`OwnedFd`], use the safe [`Self::try_from_owned_fd()`] instead.
+	///
+	/// # Safety
+	/// This function is unsafe because taking ownership of a raw file descriptor can not be checked:
+	/// an open file descriptor may still be owned and closed by another object, like a [`std::fs::File`].
+	/// The file descriptor must not be owned by any other object,
+	/// and it must not be closed as long as it is managed by the created [`FileDesc`].
+	pub unsafe fn try_from_raw_fd(fd: RawFd, options: &WrapOptions) -> Result<Self, WrapError> {
+		let error = |kind| WrapError { fd, kind };
+
+		if fd < 0 {
+			return Err(error(WrapErrorKind::Negative));
+		}
+		if !options.allow_stdio && fd <= 2 {
+			return Err(error(WrapErrorKind::Stdio));
+		}
+		if let Err(e) = unsafe { check_ret(libc::fcntl(fd, libc::F_GETFD)) } {
+			if e.raw_os_error() == Some(libc::EBADF) {
+				return Err(error(WrapErrorKind::NotOpen));
+			} else {
+				return Err(error(WrapErrorKind::Io(e)));
+			}
+		}
+
+		let wrapped = unsafe { Self::from_raw_fd(fd) };
+		match check_wrapped(&wrapped, options) {
+			Ok(()) => Ok(wrapped),
+			Err(kind) => {
+				// Do not close the file descriptor if it is rejected.
+				let _ = wrapped.into_raw_fd();
+				Err(error(kind))
+			},
+		}
+	}
+
+	/// Wrap an owned file descriptor in a [`FileDesc`] after validating it.
+	///
+	/// This performs the same checks as [`Self::try_from_raw_fd()`].
+	/// If a check fails, the file descriptor is closed and a [`WrapError`] is returned.
+	pub fn try_from_owned_fd(fd: OwnedFd, options: &WrapOptions) -> Result<Self, WrapError> {
+		// The file descriptor is owned by `fd`, so it is safe to transfer ownership to the new FileDesc.
+		// Rejected file descriptors are not closed by `try_from_raw_fd()`, so close them here.
+		let raw = fd.into_raw_fd();
+		unsafe {
+			Self::try_from_raw_fd(raw, options).inspect_err(|_| drop(OwnedFd::from_raw_fd(raw)))
+		}
+	}
+}
+
+/// Check if a wrapped file descriptor satisfies the file type and access mode requirements.
+fn check_wrapped(fd: &FileDesc, options: &WrapOptions) -> Result<(), WrapErrorKind> {
+	if let Some(expected) = options.file_type {
+		let actual = fd.file_type().map_err(WrapErrorKind::Io)?;
+		if actual != expected && fd.stat().map_err(WrapErrorKind::Io)?.file_type() != expected {
+			return Err(WrapErrorKind::FileType { expected, actual });
+		}
+	}
+
+	if let Some(expected) = options.access_mode {
+		let actual = fd.access_mode().map_err(WrapErrorKind::Io)?;
+		if !actual.allows(expected) {
+			return Err(WrapErrorKind::AccessMode { expected, actual });
+		}
+	}
+
+	Ok(())
+}